@@ -0,0 +1,150 @@
+//! Benchmarks for the automaton's reasoning, conflict resolution and whole rounds, plus the two
+//! ways of finding marked cells: the board's lists, or a scan of the whole grid. Raycasts and
+//! automaton moves are timed on both a `Board` and a `BitBoard`.
+
+use automatafl::*;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+fn c(x: u16, y: u16) -> Coord {
+    Coord { x, y }
+}
+
+fn mv(who: u8, from: Coord, to: Coord) -> Move {
+    Move {
+        who: Pid(who),
+        from,
+        to,
+    }
+}
+
+/// A big board with particles scattered over it, so rays have some way to go.
+fn sparse_board() -> Board {
+    let mut board = Board::new(64, 64);
+    for i in 0..64u16 {
+        let at = c(i * 7 % 64, i * 13 % 64);
+        if board.particles[at.ix()].what() == Particle::Vacuum {
+            let what = if i % 2 == 0 {
+                Particle::Attractor
+            } else {
+                Particle::Repulsor
+            };
+            board.place(at, what);
+        }
+    }
+    board
+}
+
+fn rays(board: &impl BoardBackend, from: Coord) -> usize {
+    [(1, 0), (-1, 0), (0, 1), (0, -1)]
+        .iter()
+        .map(|&(dx, dy)| board.raycast(from, Delta::new(dx, dy)).dist)
+        .sum()
+}
+
+fn raycast(cr: &mut Criterion) {
+    let mut group = cr.benchmark_group("raycast");
+    for (name, board) in &[
+        ("stock", Board::stock_two_player()),
+        ("sparse", sparse_board()),
+    ] {
+        let from = board.automatons[0];
+        group.bench_function(*name, |b| b.iter(|| rays(board, from)));
+        let bits = BitBoard::from_board(board).unwrap();
+        group.bench_function(format!("{}_bitboard", name), |b| {
+            b.iter(|| rays(&bits, from))
+        });
+    }
+    group.finish();
+}
+
+fn automaton_move(cr: &mut Criterion) {
+    let mut group = cr.benchmark_group("automaton_move");
+    for (name, board) in &[
+        ("stock", Board::stock_two_player()),
+        ("sparse", sparse_board()),
+    ] {
+        let game = Game::new(board.clone(), 2, RuleSet::default()).unwrap();
+        group.bench_function(*name, |b| b.iter(|| game.explain_automaton().to));
+        let bits = BitBoard::from_board(board).unwrap();
+        group.bench_function(format!("{}_bitboard", name), |b| {
+            b.iter_batched_ref(
+                || bits.clone(),
+                |bits| game.step_automatons(bits)[0].to,
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn rounds(cr: &mut Criterion) {
+    let game = Game::new(Board::stock_two_player(), 2, RuleSet::default()).unwrap();
+    let play = |moves: [Move; 2]| {
+        move |mut game: Game| {
+            for &m in &moves {
+                game.propose_move(m);
+            }
+            game.try_complete_round().is_ok()
+        }
+    };
+
+    // Both players try to move a repulsor onto the same square.
+    cr.bench_function("resolve_conflicts", |b| {
+        b.iter_batched(
+            || game.clone(),
+            play([mv(0, c(0, 1), c(0, 2)), mv(1, c(0, 4), c(0, 2))]),
+            BatchSize::SmallInput,
+        )
+    });
+    cr.bench_function("full_round", |b| {
+        b.iter_batched(
+            || game.clone(),
+            play([mv(0, c(0, 1), c(0, 2)), mv(1, c(10, 1), c(10, 2))]),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn marks(cr: &mut Criterion) {
+    // A conflict as left by resolve_conflicts, on a board big enough for scanning to matter.
+    let mut game = Game::new(sparse_board(), 2, RuleSet::default()).unwrap();
+    game.propose_move(mv(0, c(0, 0), c(0, 1)));
+    game.propose_move(mv(1, c(0, 0), c(0, 2)));
+    game.try_complete_round().unwrap_err();
+    game.board.particles[c(10, 10).ix()].set_passable(true);
+    game.board.passable_list.push(c(10, 10));
+    let board = game.board;
+    assert!(!board.conflict_list.is_empty());
+
+    let mut group = cr.benchmark_group("marks");
+    group.bench_function("list", |bn| {
+        bn.iter_batched_ref(
+            || board.clone(),
+            |board| {
+                for c in board.conflict_list.drain(..) {
+                    board.particles[c.ix()].set_conflict(false);
+                }
+                for c in board.passable_list.drain(..) {
+                    board.particles[c.ix()].set_passable(false);
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("scan", |bn| {
+        bn.iter_batched_ref(
+            || board.clone(),
+            |board| {
+                for cell in board.particles.iter_mut() {
+                    cell.set_conflict(false);
+                    cell.set_passable(false);
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, raycast, automaton_move, rounds, marks);
+criterion_main!(benches);
@@ -0,0 +1,521 @@
+//! Board backends, and a bitboard one for fast simulation.
+//!
+//! `BoardBackend` is what the automaton needs to work out its step (see `Game::explain_step`) and
+//! what moving a piece needs. `Board` is the reference backend, walking its grid a cell at a time.
+//! `BitBoard` keeps a bitmask per kind of particle for every row and column instead, so finding
+//! the nearest particle along a line is a leading or trailing zero count per 64 cells.
+//!
+//! A `BitBoard` only holds what these need: the particles, the terrain and the passable marks.
+//! It supports square tilings, flat or toroidal.
+
+use crate::*;
+use smallvec::SmallVec;
+
+/// Storage for a board that automatons can step on and pieces can move around.
+pub trait BoardBackend: core::fmt::Debug {
+    fn tiling(&self) -> Tiling;
+
+    /// Where the automatons are, ordered by x and then y.
+    fn automatons(&self) -> &[Coord];
+
+    /// See `Board::offset`.
+    fn offset(&self, c: Coord, d: Delta) -> Option<Coord>;
+
+    /// See `Board::raycast`.
+    fn raycast(&self, from: Coord, axis: Delta) -> Raycast;
+
+    /// Move a piece if the rule allows it and the way is clear, as in conflict resolution.
+    fn do_move(&mut self, from: Coord, to: Coord, rule: &impl MovementRule) -> MoveResult;
+}
+
+impl BoardBackend for Board {
+    fn tiling(&self) -> Tiling {
+        self.tiling
+    }
+
+    fn automatons(&self) -> &[Coord] {
+        &self.automatons
+    }
+
+    fn offset(&self, c: Coord, d: Delta) -> Option<Coord> {
+        Board::offset(self, c, d)
+    }
+
+    fn raycast(&self, from: Coord, axis: Delta) -> Raycast {
+        Board::raycast(self, from, axis)
+    }
+
+    fn do_move(&mut self, from: Coord, to: Coord, rule: &impl MovementRule) -> MoveResult {
+        Board::do_move(self, from, to, rule)
+    }
+}
+
+/// One bit per cell of a board's rows (indexed by y, with a bit for each x) and columns (indexed
+/// by x, with a bit for each y).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Lines {
+    rows: Vec<Vec<u64>>,
+    columns: Vec<Vec<u64>>,
+}
+
+impl Lines {
+    fn new(size: Coord) -> Lines {
+        let line = |len: u16| vec![0; (len as usize).div_ceil(64)];
+        Lines {
+            rows: vec![line(size.x); size.y as usize],
+            columns: vec![line(size.y); size.x as usize],
+        }
+    }
+
+    fn get(&self, c: Coord) -> bool {
+        let (x, y) = c.ix();
+        self.rows[y][x / 64] & 1 << (x % 64) != 0
+    }
+
+    fn set(&mut self, c: Coord, on: bool) {
+        let (x, y) = c.ix();
+        for (word, bit) in [
+            (&mut self.rows[y][x / 64], x % 64),
+            (&mut self.columns[x][y / 64], y % 64),
+        ] {
+            if on {
+                *word |= 1 << bit;
+            } else {
+                *word &= !(1 << bit);
+            }
+        }
+    }
+
+    /// The line through a cell along an axis, and the cell's place on it.
+    fn line(&self, c: Coord, horizontal: bool) -> (&[u64], usize) {
+        if horizontal {
+            (&self.rows[c.y as usize], c.x as usize)
+        } else {
+            (&self.columns[c.x as usize], c.y as usize)
+        }
+    }
+}
+
+/// The words covering bits `lo..hi`, each with its index and the bits outside the range cleared.
+fn masked(
+    words: &[u64],
+    lo: usize,
+    hi: usize,
+) -> impl DoubleEndedIterator<Item = (usize, u64)> + '_ {
+    let last = hi.saturating_sub(1) / 64;
+    words
+        .iter()
+        .enumerate()
+        .take(last + 1)
+        .skip(lo / 64)
+        .filter(move |_| lo < hi)
+        .map(move |(i, &word)| {
+            let mut word = word;
+            if i == lo / 64 {
+                word &= !0 << (lo % 64);
+            }
+            if i == last && !hi.is_multiple_of(64) {
+                word &= !(!0 << (hi % 64));
+            }
+            (i, word)
+        })
+}
+
+/// The lowest set bit in `lo..hi`.
+fn lowest(words: &[u64], lo: usize, hi: usize) -> Option<usize> {
+    masked(words, lo, hi)
+        .find(|&(_, word)| word != 0)
+        .map(|(i, word)| i * 64 + word.trailing_zeros() as usize)
+}
+
+/// The highest set bit in `lo..hi`.
+fn highest(words: &[u64], lo: usize, hi: usize) -> Option<usize> {
+    masked(words, lo, hi)
+        .rev()
+        .find(|&(_, word)| word != 0)
+        .map(|(i, word)| i * 64 + 63 - word.leading_zeros() as usize)
+}
+
+/// A board stored as bitmasks, for simulations that spend their time raycasting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitBoard {
+    size: Coord,
+    topology: Topology,
+    automatons: SmallVec<[Coord; 1]>,
+    /// Where each kind of particle is, indexed by `Particle as usize`. Vacuum's are left empty.
+    kinds: Vec<Lines>,
+    /// Everything that stops a ray: particles, walls and holes.
+    stops: Lines,
+    walls: Lines,
+    holes: Lines,
+    passable: Lines,
+}
+
+impl BitBoard {
+    /// The same position as a board, or None if it isn't square-tiled.
+    pub fn from_board(board: &Board) -> Option<BitBoard> {
+        if board.tiling != Tiling::Square {
+            return None;
+        }
+        let empty = Lines::new(board.size);
+        let mut bits = BitBoard {
+            size: board.size,
+            topology: board.topology,
+            automatons: board.automatons.clone(),
+            kinds: vec![empty.clone(); Particle::ALL.len()],
+            stops: empty.clone(),
+            walls: empty.clone(),
+            holes: empty.clone(),
+            passable: empty,
+        };
+        for ((x, y), cell) in board.particles.indexed_iter() {
+            let c = Coord {
+                x: x as u16,
+                y: y as u16,
+            };
+            bits.put(c, cell.what());
+            match cell.terrain() {
+                Terrain::Floor => {}
+                Terrain::Wall => bits.walls.set(c, true),
+                Terrain::Hole => bits.holes.set(c, true),
+            }
+            if cell.terrain() != Terrain::Floor {
+                bits.stops.set(c, true);
+            }
+            bits.passable.set(c, cell.passable());
+        }
+        Some(bits)
+    }
+
+    pub fn size(&self) -> Coord {
+        self.size
+    }
+
+    /// The particle on a cell.
+    pub fn what(&self, c: Coord) -> Particle {
+        Particle::ALL
+            .iter()
+            .copied()
+            .find(|&p| !p.is_vacuum() && self.kinds[p as usize].get(c))
+            .unwrap_or(Particle::Vacuum)
+    }
+
+    fn inbounds(&self, c: Coord) -> bool {
+        c.x < self.size.x && c.y < self.size.y && !self.holes.get(c)
+    }
+
+    /// Put a particle on a floor cell, which must hold vacuum, without touching the automaton list.
+    fn put(&mut self, c: Coord, what: Particle) {
+        if !what.is_vacuum() {
+            self.kinds[what as usize].set(c, true);
+            self.stops.set(c, true);
+        }
+    }
+
+    /// Take whatever particle is on a cell off it, leaving vacuum.
+    fn take(&mut self, c: Coord) -> Particle {
+        let what = self.what(c);
+        if !what.is_vacuum() {
+            self.kinds[what as usize].set(c, false);
+            self.stops.set(c, false);
+        }
+        what
+    }
+
+    /// How many steps from a cell along a line to the nearest cell that stops a ray, or to
+    /// `from`, whichever comes first. On a flat board, None if the ray reaches the edge first.
+    fn nearest_stop(&self, co: Coord, d: Delta, from: Coord) -> Option<usize> {
+        let horizontal = d.dx() != 0;
+        let (words, pos) = self.stops.line(co, horizontal);
+        let len = if horizontal { self.size.x } else { self.size.y } as usize;
+        let (on_line, from_pos) = if horizontal {
+            (from.y == co.y, from.x as usize)
+        } else {
+            (from.x == co.x, from.y as usize)
+        };
+        nearest::steps_to_stop(
+            len,
+            pos,
+            d.dx() + d.dy() > 0,
+            self.topology,
+            Some(from_pos).filter(|_| on_line),
+            |lo, hi| lowest(words, lo, hi),
+            |lo, hi| highest(words, lo, hi),
+        )
+    }
+}
+
+impl BoardBackend for BitBoard {
+    fn tiling(&self) -> Tiling {
+        Tiling::Square
+    }
+
+    fn automatons(&self) -> &[Coord] {
+        &self.automatons
+    }
+
+    fn offset(&self, c: Coord, d: Delta) -> Option<Coord> {
+        self.topology.offset(self.size, c, d)
+    }
+
+    fn raycast(&self, from: Coord, axis: Delta) -> Raycast {
+        debug_assert_eq!(Tiling::Square.distance(axis), 1);
+
+        let index = |d: Delta| if d.dx() != 0 { 0 } else { 1 };
+        let (mut co, mut d, mut dist, mut clear) = (from, axis, 0, None);
+        loop {
+            let steps = match self.nearest_stop(co, d, from) {
+                Some(steps) => steps,
+                None => {
+                    // Off the edge of a flat board.
+                    let (pos, len) = if d.dx() != 0 {
+                        (co.x, self.size.x)
+                    } else {
+                        (co.y, self.size.y)
+                    };
+                    let steps = if d.dx() + d.dy() > 0 {
+                        len - pos
+                    } else {
+                        pos + 1
+                    };
+                    return Raycast {
+                        what: Particle::Vacuum,
+                        hit: None,
+                        dist: dist + steps as usize,
+                        axis: index(d),
+                        clear: clear.unwrap_or(dist + steps as usize - 1),
+                    };
+                }
+            };
+            dist += steps;
+            co = self
+                .offset(co, d * steps as isize)
+                .expect("stops are on the board");
+
+            // Walls and holes look just like the edge of the board.
+            if self.walls.get(co) || self.holes.get(co) {
+                return Raycast {
+                    what: Particle::Vacuum,
+                    hit: None,
+                    dist,
+                    axis: index(d),
+                    clear: clear.unwrap_or(dist - 1),
+                };
+            }
+            let what = if co == from {
+                Particle::Automaton
+            } else {
+                self.what(co)
+            };
+            if clear.is_none() {
+                clear = Some(dist - 1);
+            }
+            match what.pass(index(d)) {
+                Some(0) if d.dx() == 0 => d = Delta::new(d.dy(), 0),
+                Some(1) if d.dy() == 0 => d = Delta::new(0, d.dx()),
+                Some(_) => {}
+                None => {
+                    return Raycast {
+                        what,
+                        hit: Some(co),
+                        dist,
+                        axis: index(d),
+                        clear: clear.unwrap_or(dist - 1),
+                    }
+                }
+            }
+        }
+    }
+
+    fn do_move(&mut self, from: Coord, to: Coord, rule: &impl MovementRule) -> MoveResult {
+        use MoveResult::*;
+
+        debug_assert!(self.inbounds(from) && self.inbounds(to));
+        debug_assert_ne!(from, to);
+
+        let delta = self.topology.displacement(self.size, from, to);
+        if !rule.allows(delta, Tiling::Square) {
+            return Disallowed;
+        }
+        for offset in rule.path(delta, Tiling::Square) {
+            let c = match self.offset(from, offset) {
+                Some(c) => c,
+                None => return Disallowed,
+            };
+            let occupied = self.stops.get(c) && !self.passable.get(c);
+            if !self.inbounds(c) || self.walls.get(c) || occupied {
+                return OccupiedAt(c);
+            }
+        }
+
+        let (a, b) = (self.take(from), self.take(to));
+        self.put(from, b);
+        self.put(to, a);
+        let mut moved = false;
+        for c in &mut self.automatons {
+            if *c == from || *c == to {
+                *c = if *c == from { to } else { from };
+                moved = true;
+            }
+        }
+        if moved {
+            self.automatons.sort_unstable_by_key(|c| (c.x, c.y));
+        }
+        Applied
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::testutil::c;
+    use crate::*;
+    use proptest::prelude::*;
+
+    fn assert_same(bits: &BitBoard, board: &Board) {
+        for ((x, y), cell) in board.particles.indexed_iter() {
+            let at = c(x as u16, y as u16);
+            assert_eq!(bits.what(at), cell.what(), "at {}", at);
+        }
+        assert_eq!(bits.automatons(), board.automatons.as_slice());
+    }
+
+    #[test]
+    fn bit_scans() {
+        let words = [1 << 3 | 1 << 63, 1 << 1];
+        assert_eq!(super::lowest(&words, 0, 128), Some(3));
+        assert_eq!(super::lowest(&words, 4, 128), Some(63));
+        assert_eq!(super::lowest(&words, 64, 65), None);
+        assert_eq!(super::lowest(&words, 64, 66), Some(65));
+        assert_eq!(super::highest(&words, 0, 128), Some(65));
+        assert_eq!(super::highest(&words, 0, 65), Some(63));
+        assert_eq!(super::highest(&words, 4, 63), None);
+        assert_eq!(super::highest(&words, 3, 3), None);
+    }
+
+    #[test]
+    fn stock_boards() {
+        assert!(BitBoard::from_board(&Board::hex(2)).is_none());
+        let game = Game::new(Board::stock_two_player(), 2, RuleSet::default()).unwrap();
+        let mut board = game.board.clone();
+        let mut bits = BitBoard::from_board(&board).unwrap();
+        assert_same(&bits, &board);
+        for _ in 0..4 {
+            let why = game.step_automatons(&mut board);
+            let bit_why = game.step_automatons(&mut bits);
+            assert_eq!(why.len(), bit_why.len());
+            for (why, bit_why) in why.iter().zip(&bit_why) {
+                assert_eq!((why.choice, why.to), (bit_why.choice, bit_why.to));
+            }
+            assert_same(&bits, &board);
+        }
+    }
+
+    /// A random square board with every kind of particle and terrain, mostly empty floor.
+    pub(crate) fn boards() -> impl Strategy<Value = Board> {
+        let cell = prop_oneof![
+            12 => Just(Cell::new(Particle::Vacuum)),
+            4 => proptest::sample::select(Particle::ALL.to_vec()).prop_map(Cell::new),
+            1 => Just(Cell::new(Particle::Vacuum).with_terrain(Terrain::Wall)),
+            1 => Just(Cell::new(Particle::Vacuum).with_terrain(Terrain::Hole)),
+        ];
+        (1usize..80, 1usize..80, any::<bool>())
+            .prop_flat_map(move |(w, h, torus)| {
+                (
+                    proptest::collection::vec(cell.clone(), w * h),
+                    Just((w, h, torus)),
+                )
+            })
+            .prop_map(|(cells, (w, h, torus))| {
+                let mut board = Board::new(w as u16, h as u16);
+                board.place(board.automatons[0], Particle::Vacuum);
+                board.particles = ndarray::Array2::from_shape_vec((w, h), cells).unwrap();
+                board.rebuild_lists();
+                if torus {
+                    board.topology = Topology::Torus;
+                }
+                board
+            })
+    }
+
+    /// An edit to a board: the coordinates of two cells, which wrap around to fit the board, and
+    /// the index of a particle to place on the first, or Vacuum to move the first to the second.
+    pub(crate) type Edit = (u16, u16, u16, u16, usize);
+
+    /// A random run of edits, to make with `apply_edits`.
+    pub(crate) fn edits() -> impl Strategy<Value = Vec<Edit>> {
+        let edit = (
+            any::<u16>(),
+            any::<u16>(),
+            any::<u16>(),
+            any::<u16>(),
+            0..Particle::ALL.len(),
+        );
+        proptest::collection::vec(edit, 1..30)
+    }
+
+    /// Make each edit to the board in turn with `Board::place` or `Board::force_move`, skipping
+    /// those that would put a particle on a wall or a hole, and check the board after each one.
+    /// `check` is also told the first of the edited cells.
+    pub(crate) fn apply_edits(
+        mut board: Board,
+        edits: Vec<Edit>,
+        mut check: impl FnMut(&Board, Coord) -> Result<(), TestCaseError>,
+    ) -> Result<(), TestCaseError> {
+        let size = board.size;
+        let cell = |x: u16, y: u16| c(x % size.x, y % size.y);
+        for (ax, ay, bx, by, what) in edits {
+            let (a, b) = (cell(ax, ay), cell(bx, by));
+            if board.particles[a.ix()].terrain() != Terrain::Floor {
+                continue;
+            }
+            let floor = board.particles[b.ix()].terrain() == Terrain::Floor;
+            if what == Particle::Vacuum as usize && floor {
+                board.force_move(a, b);
+            } else {
+                board.place(a, Particle::ALL[what]);
+            }
+            check(&board, a)?;
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(200))]
+
+        #[test]
+        fn matches_grid_raycasts(board in boards(), x: u16, y: u16) {
+            let bits = BitBoard::from_board(&board).unwrap();
+            assert_same(&bits, &board);
+            let from = c(x % board.size.x, y % board.size.y);
+            prop_assume!(board.inbounds(from));
+            for &(dx, dy) in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let axis = Delta::new(dx, dy);
+                prop_assert_eq!(bits.raycast(from, axis), board.raycast(from, axis));
+            }
+        }
+
+        #[test]
+        fn matches_grid_moves(
+            board in boards(),
+            moves in proptest::collection::vec((any::<u16>(), any::<u16>(), any::<u16>(), any::<u16>(), 0usize..5), 1..20),
+        ) {
+            use Movement::*;
+            let mut board = board;
+            let mut bits = BitBoard::from_board(&board).unwrap();
+            let size = board.size;
+            let cell = |x: u16, y: u16| c(x % size.x, y % size.y);
+            for (fx, fy, tx, ty, rule) in moves {
+                let (from, to) = (cell(fx, fy), cell(tx, ty));
+                let rule = [Rook, Bishop, Knight, King, Teleport][rule];
+                if from == to || !board.inbounds(from) || !board.inbounds(to) {
+                    continue;
+                }
+                if board.particles[from.ix()].what() == Particle::Vacuum {
+                    continue;
+                }
+                prop_assert_eq!(bits.do_move(from, to, &rule), board.do_move(from, to, &rule));
+                assert_same(&bits, &board);
+            }
+        }
+    }
+}
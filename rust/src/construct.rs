@@ -0,0 +1,361 @@
+//! Building boards, and checking that a board's parts agree with each other.
+//!
+//! `Board` keeps some of its state twice over: the size alongside the grid's shape, and the lists
+//! of automatons and marked cells alongside the cells themselves. The constructors here keep them
+//! in step; a board put together by hand can be checked with `Board::validate`, which `Game::new`
+//! does before accepting one.
+
+use crate::*;
+use displaydoc::Display;
+use ndarray::Array2 as Grid;
+use smallvec::{smallvec, SmallVec};
+
+/// {}
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BoardError {
+    /// the board has no cells
+    Empty,
+    /// line {line} has {found} cells, but the first has {expected}
+    Ragged {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// the board is too big: at most 65535 cells each way
+    TooBig,
+    /// the size says {size} but the grid is {width}x{height}
+    SizeMismatch {
+        size: Coord,
+        width: usize,
+        height: usize,
+    },
+    /// there is no automaton on the board
+    NoAutomaton,
+    /// the automaton list doesn't match the automatons on the board
+    AutomatonList,
+    /// the conflict list doesn't match the conflicted cells
+    ConflictList,
+    /// the passable list doesn't match the passable cells
+    PassableList,
+    /// the line index doesn't match the cells
+    LineIndex,
+    /// the position hash doesn't match the cells
+    Hash,
+    /// there's a particle off the floor at {0}
+    OffFloor(Coord),
+    /// the board has no stock goals for {0} players
+    PlayerCount(u8),
+    /// the size says {size} but the hyper board's shape needs {expected}
+    ShapeMismatch { size: Coord, expected: Coord },
+    /// there's floor at {0}, between the layers of a hyper board
+    Padding(Coord),
+    /// a hyper board can't wrap around
+    HyperTorus,
+}
+
+/// Everything wrong with a board, as found by `Board::validate`, or by the constructors in
+/// `setup` when the board has no stock goals for a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct BoardErrors {
+    pub data: SmallVec<[BoardError; 2]>,
+}
+
+impl core::fmt::Display for BoardErrors {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Inconsistent board: ")?;
+        for (i, error) in self.data.iter().enumerate() {
+            if i != 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<BoardError> for BoardErrors {
+    fn from(error: BoardError) -> BoardErrors {
+        BoardErrors {
+            data: smallvec![error],
+        }
+    }
+}
+
+impl Board {
+    /// An empty board of the given size, with an automaton in the middle (rounding down).
+    ///
+    /// A size of zero either way makes a board that won't validate.
+    pub fn new(width: u16, height: u16) -> Board {
+        let mut board = Board::from_grid(Grid::from_elem(
+            (width as usize, height as usize),
+            Cell::new(Particle::Vacuum),
+        ));
+        if width != 0 && height != 0 {
+            board.place(
+                Coord {
+                    x: width / 2,
+                    y: height / 2,
+                },
+                Particle::Automaton,
+            );
+        }
+        board
+    }
+
+    /// A board with the given particles, one slice for each row from y = 0 upward, each listing
+    /// the cells from x = 0 along.
+    pub fn from_rows<R: AsRef<[Particle]>>(rows: &[R]) -> Result<Board, BoardErrors> {
+        let (height, width) = rectangle(rows)?;
+        let board = Board::from_grid(Grid::from_shape_fn((width, height), |(x, y)| {
+            Cell::new(rows[y].as_ref()[x])
+        }));
+        board.validate()?;
+        Ok(board)
+    }
+
+    /// A board with the given particles, one slice for each column from x = 0 along, each listing
+    /// the cells from y = 0 upward. This is the way round of `Board::particles`, and of the stock
+    /// boards' literals.
+    pub fn from_columns<C: AsRef<[Particle]>>(columns: &[C]) -> Result<Board, BoardErrors> {
+        let (width, height) = rectangle(columns)?;
+        let board = Board::from_grid(Grid::from_shape_fn((width, height), |(x, y)| {
+            Cell::new(columns[x].as_ref()[y])
+        }));
+        board.validate()?;
+        Ok(board)
+    }
+
+    /// A flat, square-tiled board with the given cells, and the lists rebuilt to match.
+    pub(crate) fn from_grid(particles: Grid<Cell>) -> Board {
+        let (w, h) = particles.dim();
+        let mut board = Board {
+            particles,
+            size: Coord {
+                x: w as u16,
+                y: h as u16,
+            },
+            automatons: SmallVec::new(),
+            conflict_list: SmallVec::new(),
+            passable_list: SmallVec::new(),
+            topology: Topology::Flat,
+            tiling: Tiling::Square,
+            index: LineIndex::default(),
+            hash: 0,
+        };
+        board.rebuild_lists();
+        board
+    }
+
+    /// Check that the board is consistent, listing everything that isn't.
+    ///
+    /// A board may have any number of automatons but must have at least one, and the lists of
+    /// automatons and marked cells must match the cells, as must the line index and the hash if
+    /// the cells were changed directly. The automaton list must be in order, but the marked cells
+    /// can be listed in any order.
+    pub fn validate(&self) -> Result<(), BoardErrors> {
+        use BoardError::*;
+
+        let (width, height) = self.particles.dim();
+        if width == 0 || height == 0 {
+            return Err(Empty.into());
+        }
+        if self.size.ix() != (width, height) {
+            return Err(SizeMismatch {
+                size: self.size,
+                width,
+                height,
+            }
+            .into());
+        }
+
+        let mut data = SmallVec::new();
+        let mut expected = self.clone();
+        expected.rebuild_lists();
+        if expected.automatons.is_empty() {
+            data.push(NoAutomaton);
+        }
+        if expected.automatons != self.automatons {
+            data.push(AutomatonList);
+        }
+        let sorted = |list: &SmallVec<[Coord; 16]>| {
+            let mut list = list.clone();
+            list.sort_unstable_by_key(|c| (c.x, c.y));
+            list
+        };
+        // A cell can be marked conflicted more than once in a round, as when three moves leave it.
+        let mut conflicts = sorted(&self.conflict_list);
+        conflicts.dedup();
+        if expected.conflict_list != conflicts {
+            data.push(ConflictList);
+        }
+        if expected.passable_list != sorted(&self.passable_list) {
+            data.push(PassableList);
+        }
+        if expected.index != self.index {
+            data.push(LineIndex);
+        }
+        if expected.hash != self.hash {
+            data.push(Hash);
+        }
+        for ((x, y), cell) in self.particles.indexed_iter() {
+            if !cell.what().is_vacuum() && cell.terrain() != Terrain::Floor {
+                data.push(OffFloor(Coord {
+                    x: x as u16,
+                    y: y as u16,
+                }));
+            }
+        }
+        if let Tiling::Hyper(shape) = self.tiling {
+            if self.topology == Topology::Torus {
+                data.push(HyperTorus);
+            }
+            if shape.size() != self.size {
+                data.push(ShapeMismatch {
+                    size: self.size,
+                    expected: shape.size(),
+                });
+            } else {
+                for ((x, y), cell) in self.particles.indexed_iter() {
+                    let c = Coord {
+                        x: x as u16,
+                        y: y as u16,
+                    };
+                    if shape.point(c).is_none() && cell.terrain() != Terrain::Hole {
+                        data.push(Padding(c));
+                    }
+                }
+            }
+        }
+
+        if data.is_empty() {
+            Ok(())
+        } else {
+            Err(BoardErrors { data })
+        }
+    }
+}
+
+/// The length and common width of a list of lines of cells, if they make a rectangle that fits
+/// on a board.
+fn rectangle<L: AsRef<[Particle]>>(lines: &[L]) -> Result<(usize, usize), BoardError> {
+    use BoardError::*;
+
+    let expected = lines.first().map_or(0, |l| l.as_ref().len());
+    if expected == 0 {
+        return Err(Empty);
+    }
+    for (line, l) in lines.iter().enumerate() {
+        let found = l.as_ref().len();
+        if found != expected {
+            return Err(Ragged {
+                line,
+                expected,
+                found,
+            });
+        }
+    }
+    if lines.len() > u16::MAX as usize || expected > u16::MAX as usize {
+        return Err(TooBig);
+    }
+    Ok((lines.len(), expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::{c, mv};
+    use crate::*;
+
+    #[test]
+    fn constructors() {
+        use Particle::{Attractor as A, Automaton as D, Repulsor as R, Vacuum as o};
+
+        assert_eq!(Board::new(5, 5), Board::stock_testing_empty());
+        let columns = [
+            [R, o, A, o, R],
+            [o; 5],
+            [R, o, D, o, R],
+            [o; 5],
+            [R, o, A, o, R],
+        ];
+        assert_eq!(Board::from_columns(&columns), Ok(Board::stock_testing()));
+
+        let rows = [[o, o, R], [D, o, o]];
+        let board = Board::from_rows(&rows).unwrap();
+        assert_eq!(board.size, Coord { x: 3, y: 2 });
+        assert_eq!(board.to_notation(), "3x2:1@/2/R1");
+
+        let ragged: [&[Particle]; 2] = [&[o, D], &[o]];
+        assert_eq!(
+            Board::from_rows(&ragged),
+            Err(BoardError::Ragged {
+                line: 1,
+                expected: 2,
+                found: 1
+            }
+            .into())
+        );
+        let empty: [[Particle; 0]; 3] = [[], [], []];
+        assert_eq!(Board::from_rows(&empty), Err(BoardError::Empty.into()));
+        assert_eq!(
+            Board::from_rows(&[[o, A]]),
+            Err(BoardError::NoAutomaton.into())
+        );
+    }
+
+    #[test]
+    fn validation() {
+        use BoardError::*;
+
+        let mut board = Board::stock_testing();
+        assert_eq!(board.validate(), Ok(()));
+
+        // Three moves from one piece conflict with each other, marking it twice.
+        let mut game = Game::new(board.clone(), 3, RuleSet::default()).unwrap();
+        game.propose_move(mv(0, c(0, 0), c(0, 1)));
+        game.propose_move(mv(1, c(0, 0), c(1, 0)));
+        game.propose_move(mv(2, c(0, 0), c(0, 3)));
+        assert!(game.try_complete_round().is_err());
+        let marks = game.board.conflict_list.iter().filter(|&&at| at == c(0, 0));
+        assert_eq!(marks.count(), 2);
+        assert_eq!(game.board.validate(), Ok(()));
+
+        board.automatons.push(Coord { x: 0, y: 0 });
+        board.passable_list.push(Coord { x: 1, y: 1 });
+        board.particles[[0, 1]].set_terrain(Terrain::Wall);
+        board.particles[[0, 2]].set_terrain(Terrain::Hole);
+        let errors = board.validate().unwrap_err();
+        assert_eq!(
+            errors.data.as_slice(),
+            &[
+                AutomatonList,
+                PassableList,
+                LineIndex,
+                Hash,
+                OffFloor(Coord { x: 0, y: 2 })
+            ]
+        );
+        assert_eq!(
+            errors.to_string(),
+            "Inconsistent board: the automaton list doesn't match the automatons on the board; \
+             the passable list doesn't match the passable cells; the line index doesn't match the \
+             cells; the position hash doesn't match the cells; there's a particle off the floor at \
+             (0, 2)"
+        );
+
+        let mut board = Board::stock_testing_empty();
+        board.size.x = 6;
+        assert!(matches!(
+            board.validate().unwrap_err().data[0],
+            SizeMismatch { width: 5, .. }
+        ));
+        let board = Board::new(0, 3);
+        assert_eq!(board.validate(), Err(Empty.into()));
+        assert!(Game::new(board, 2, RuleSet::default()).is_err());
+    }
+}
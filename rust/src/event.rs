@@ -0,0 +1,77 @@
+//! Events describing everything that happens in a game, in order.
+//!
+//! These mirror the output events of the Python prototype (MOVE_ACK, MOVE_INVALID, MOVE,
+//! CONFLICT and TURN_OVER), so that frontends and servers can be driven from a single stream
+//! instead of picking apart return values. The `Game` queues them up as it goes, if its
+//! `emit_events` flag is set; call `Game::drain_events` to take them.
+
+use crate::*;
+use smallvec::SmallVec;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameEvent {
+    /// A player claimed a corner as one of their goals, during setup.
+    CornerClaimed { who: Pid, at: Coord },
+    /// A player's move was accepted and is pending. `replaced` is set if it took the place of an
+    /// earlier pending move.
+    MoveAck { m: Move, replaced: bool },
+    /// A player's move was rejected.
+    MoveInvalid { who: Pid, feedback: MoveFeedback },
+    /// A player took back their pending move.
+    MoveWithdrawn { who: Pid },
+    /// Some players' moves conflicted on a square, which is now off-limits until the round ends.
+    Conflict {
+        at: Coord,
+        players: SmallVec<[Pid; 2]>,
+    },
+    /// A locked move was carried out, successfully or not.
+    DoMove { m: Move, result: MoveResult },
+    /// An automaton stepped (or stayed put, if `from == to`).
+    AutomatonMove { from: Coord, to: Coord },
+    /// The round is over, and maybe the game too.
+    TurnOver {
+        automatons_from: SmallVec<[Coord; 1]>,
+        automatons_to: SmallVec<[Coord; 1]>,
+        winner: Option<Pid>,
+    },
+    /// The last completed round was taken back, along with any moves pending since.
+    RoundUndone,
+    /// The last round taken back was put back, discarding any moves pending since.
+    RoundRedone,
+}
+
+impl core::fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        use GameEvent::*;
+        match self {
+            CornerClaimed { who, at } => write!(f, "Player {} claimed the corner at {}", who.0, at),
+            MoveAck { m, replaced } => {
+                let verb = if *replaced { "replaced" } else { "submitted" };
+                write!(f, "Player {} {} a move", m.who.0, verb)
+            }
+            MoveInvalid { who, feedback } => write!(f, "Player {}: {}", who.0, feedback),
+            MoveWithdrawn { who } => write!(f, "Player {} withdrew their move", who.0),
+            Conflict { at, players } => {
+                write!(f, "Conflict at {} between players", at)?;
+                for p in players {
+                    write!(f, " {}", p.0)?;
+                }
+                Ok(())
+            }
+            DoMove { m, result } => write!(
+                f,
+                "Player {} move from {} to {} {}",
+                m.who.0, m.from, m.to, result
+            ),
+            AutomatonMove { from, to } if from == to => write!(f, "Automaton stayed at {}", to),
+            AutomatonMove { from, to } => write!(f, "Automaton moved from {} to {}", from, to),
+            TurnOver {
+                winner: Some(who), ..
+            } => write!(f, "Turn over, player {} wins!", who.0),
+            TurnOver { winner: None, .. } => write!(f, "Turn over"),
+            RoundUndone => write!(f, "Took back the last round"),
+            RoundRedone => write!(f, "Put back the last round"),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Hexagonal boards.
+//!
+//! A hex board keeps the same grid of cells as a square one, read in axial coordinates: each cell
+//! touches the cells one step along x, along y, and along the diagonal that increases x while
+//! decreasing y. Those are the three axes pieces move along and the automaton looks along, and
+//! they're numbered in that order, so the column rule's "last tied axis" becomes the diagonal.
+//!
+//! Holes (see `Terrain::Hole`) cut the grid down to a hexagon, as in `Board::hex`; everything else,
+//! from particles to conflict resolution, works just as on a square board.
+
+use crate::*;
+use ndarray::Array2 as Grid;
+
+/// How the cells of a board fit together.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Tiling {
+    /// Squares, each touching four others.
+    #[default]
+    Square,
+    /// Hexagons, each touching six others.
+    Hex,
+    /// Hypercubes, with the axes after the first two folded into y (see `Board::hyper`).
+    Hyper(Shape),
+}
+
+impl Tiling {
+    pub fn is_square(&self) -> bool {
+        *self == Tiling::Square
+    }
+
+    /// A unit delta in the positive direction along each axis.
+    pub fn axes(self) -> impl ExactSizeIterator<Item = Delta> + Clone {
+        let count = match self {
+            Tiling::Square => 2,
+            Tiling::Hex => 3,
+            Tiling::Hyper(shape) => shape.axes(),
+        };
+        (0..count).map(move |i| self.axis(i))
+    }
+
+    /// A unit delta in the positive direction along one axis.
+    pub fn axis(self, i: usize) -> Delta {
+        match (self, i) {
+            (_, 0) => Delta::XP,
+            (_, 1) => Delta::YP,
+            (Tiling::Hex, 2) => Delta::ZP,
+            (Tiling::Hyper(shape), _) => Delta::new(0, shape.stride(i) as i32),
+            _ => panic!("no axis {} on a {:?} board", i, self),
+        }
+    }
+
+    /// Which axis a delta lies along, either way, if any.
+    pub fn axis_of(self, d: Delta) -> Option<usize> {
+        if let Tiling::Hyper(shape) = self {
+            let parts = shape.split(d);
+            let mut moved = (0..shape.axes()).filter(|&i| parts[i] != 0);
+            return match (moved.next(), moved.next()) {
+                (Some(i), None) => Some(i),
+                _ => None,
+            };
+        }
+        match (d.dx, d.dy) {
+            (0, 0) => None,
+            (_, 0) => Some(0),
+            (0, _) => Some(1),
+            (dx, dy) if self == Tiling::Hex && dx == -dy => Some(2),
+            _ => None,
+        }
+    }
+
+    /// How many steps apart two cells are a delta away from each other, moving to a neighbouring
+    /// cell each step.
+    pub fn distance(self, d: Delta) -> usize {
+        let (dx, dy) = (d.dx as isize, d.dy as isize);
+        let steps = match self {
+            Tiling::Square => dx.abs() + dy.abs(),
+            Tiling::Hex => dx.abs().max(dy.abs()).max((dx + dy).abs()),
+            Tiling::Hyper(_) => self.components(d).sum::<i32>() as isize,
+        };
+        steps as usize
+    }
+
+    /// How far a delta goes along each axis it goes along at all, in order of axis. On a hex
+    /// board, that's along x and y only.
+    pub(crate) fn components(self, d: Delta) -> impl Iterator<Item = i32> + Clone {
+        let (parts, count) = match self {
+            Tiling::Hyper(shape) => (shape.split(d), shape.axes()),
+            Tiling::Square | Tiling::Hex => {
+                let mut parts = [0; Shape::MAX_AXES];
+                parts[0] = d.dx;
+                parts[1] = d.dy;
+                (parts, 2)
+            }
+        };
+        (0..count).map(move |i| parts[i].abs()).filter(|&p| p != 0)
+    }
+}
+
+impl Board {
+    /// An empty hexagonal board, with the given number of cells from the middle to each edge, and
+    /// an automaton in the middle.
+    ///
+    /// Panics if the board would be too big for a coordinate, with a radius over 32767.
+    pub fn hex(radius: u16) -> Board {
+        let width = radius
+            .checked_mul(2)
+            .and_then(|w| w.checked_add(1))
+            .expect("hex board too big");
+        let middle = Coord {
+            x: radius,
+            y: radius,
+        };
+        let particles = Grid::from_shape_fn((width as usize, width as usize), |(x, y)| {
+            let at = Coord {
+                x: x as u16,
+                y: y as u16,
+            };
+            let outside = Tiling::Hex.distance(at - middle) > radius as usize;
+            let what = if at == middle {
+                Particle::Automaton
+            } else {
+                Particle::Vacuum
+            };
+            let terrain = if outside {
+                Terrain::Hole
+            } else {
+                Terrain::Floor
+            };
+            Cell::new(what).with_terrain(terrain)
+        });
+        let mut board = Board {
+            particles,
+            size: Coord { x: width, y: width },
+            automatons: SmallVec::new(),
+            conflict_list: SmallVec::new(),
+            passable_list: SmallVec::new(),
+            topology: Topology::Flat,
+            tiling: Tiling::Hex,
+            index: LineIndex::default(),
+            hash: 0,
+        };
+        board.rebuild_lists();
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::{c, mv};
+    use crate::*;
+
+    #[test]
+    fn hex_board() {
+        let board = Board::hex(2);
+        assert_eq!(board.automatons.as_slice(), &[c(2, 2)]);
+        assert_eq!(board.to_notation(), "5x5h:__3/_4/2@2/4_/3__");
+        assert!(board.inbounds(c(4, 0)));
+        assert!(!board.inbounds(c(4, 4)));
+        assert_eq!(board.goal_sites(), [c(2, 0), c(4, 0), c(2, 4), c(0, 4)]);
+        assert_eq!(Board::from_notation(&board.to_notation()), Ok(board));
+        let torus = Board::from_notation("5x5h~:5/5/2@2/5/5").unwrap();
+        assert_eq!(
+            (torus.tiling, torus.topology),
+            (Tiling::Hex, Topology::Torus)
+        );
+        assert_eq!(torus.to_notation(), "5x5h~:5/5/2@2/5/5");
+    }
+
+    #[test]
+    fn three_axes() {
+        let mut game = Game::new(Board::hex(3), 2, RuleSet::default()).unwrap();
+        // An attractor two steps off along the diagonal, and another three off along the row.
+        game.board.place(c(5, 1), Particle::Attractor);
+        game.board.place(c(6, 3), Particle::Attractor);
+        let why = game.explain_automaton();
+        assert_eq!(why.axes.len(), 3);
+        assert_eq!(why.choice, AxisChoice::Urgent(2));
+        assert_eq!(why.to, c(4, 2));
+
+        // Tied with one as far off along the column, the diagonal wins by the column rule.
+        game.board.place(c(3, 1), Particle::Attractor);
+        let why = game.explain_automaton();
+        assert_eq!(why.choice, AxisChoice::ColumnRule(2));
+        assert_eq!(why.to, c(4, 2));
+        assert!(
+            why.to_string().contains("Went with the diagonal"),
+            "{}",
+            why
+        );
+        game.board.place(c(3, 1), Particle::Vacuum);
+
+        // Rooks move along the diagonal too, but not across it.
+        assert_eq!(
+            game.propose_move(mv(0, c(6, 3), c(4, 5))).0,
+            MoveFeedback::Committed
+        );
+        assert_eq!(
+            game.propose_move(mv(1, c(5, 1), c(6, 2))).0,
+            MoveFeedback::Disallowed(Movement::Rook)
+        );
+        assert_eq!(
+            game.propose_move(mv(1, c(5, 1), c(5, 2))).0,
+            MoveFeedback::Committed
+        );
+        let results = game.try_complete_round().unwrap();
+        assert!(results.iter().all(|(_, r)| *r == MoveResult::Applied));
+        assert_eq!(
+            game.board.particles[c(4, 5).ix()].what(),
+            Particle::Attractor
+        );
+    }
+}
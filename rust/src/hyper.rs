@@ -0,0 +1,448 @@
+//! Hyperautomatafl: boards with three or more dimensions, the first extension the README lists.
+//!
+//! A hyper board keeps the same grid of cells as a square one, with every axis after the first
+//! folded into y: axis 0 is x, axis 1 runs up y within a layer, and each further axis steps from a
+//! layer to the one stacked above it, a stride of `Shape::stride` along y. Between the layers lie
+//! rows of holes (see `Terrain::Hole`), enough of them that the delta between two cells can only
+//! be read one way, so the movement rules tell from a delta alone which axes a move runs along.
+//!
+//! Pieces move along any one axis like rooks (and bishops, knights and kings generalize the same
+//! way), and the automaton makes its decision along every axis as it does along the row and the
+//! column, going with the most urgent. When several tie, the column rule picks the last of them.
+//! Everything else, from conflicts to goals and records, works just as on a square board, in the
+//! folded coordinates. In board notation the size lists every axis, as in `3x3x3`. Hyper boards
+//! are always flat.
+
+use crate::*;
+use displaydoc::Display;
+use ndarray::Array2 as Grid;
+
+/// How many cells a hyper board has along each of its axes.
+///
+/// Each axis must be at least two cells long, and there must be at least three of them. The folded
+/// board must fit in a coordinate, which leaves room for eleven axes at most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "Vec<u16>", into = "Vec<u16>")
+)]
+pub struct Shape {
+    len: u8,
+    /// The extent along each axis, then zeroes.
+    extents: [u16; Shape::MAX_AXES],
+}
+
+/// Bad shape for a hyper board: {}
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShapeError {
+    /// it needs at least three axes
+    TooFewAxes,
+    /// axis {0} is shorter than two cells
+    TooShort(usize),
+    /// it's too big to fold into a coordinate
+    TooBig,
+}
+
+impl Shape {
+    pub const MAX_AXES: usize = 11;
+
+    pub fn new(extents: &[u16]) -> Result<Shape, ShapeError> {
+        if extents.len() < 3 {
+            return Err(ShapeError::TooFewAxes);
+        }
+        if let Some(axis) = extents.iter().position(|&n| n < 2) {
+            return Err(ShapeError::TooShort(axis));
+        }
+        if extents.len() > Shape::MAX_AXES {
+            return Err(ShapeError::TooBig);
+        }
+        let mut shape = Shape {
+            len: extents.len() as u8,
+            extents: [0; Shape::MAX_AXES],
+        };
+        shape.extents[..extents.len()].copy_from_slice(extents);
+        // The height is the last cell's y, plus one.
+        let top = (1..shape.axes()).try_fold(1u32, |top, i| {
+            Some(top + (shape.extents[i] as u32 - 1) * shape.wide_stride(i)?)
+        });
+        match top {
+            Some(top) if top <= u16::MAX as u32 => Ok(shape),
+            _ => Err(ShapeError::TooBig),
+        }
+    }
+
+    /// How many axes there are.
+    pub fn axes(&self) -> usize {
+        self.len as usize
+    }
+
+    /// The extent along each axis.
+    pub fn extents(&self) -> &[u16] {
+        &self.extents[..self.axes()]
+    }
+
+    /// The size of the folded board.
+    pub fn size(&self) -> Coord {
+        let top: u32 = (1..self.axes())
+            .map(|i| (self.extents[i] as u32 - 1) * self.stride(i) as u32)
+            .sum();
+        Coord {
+            x: self.extents[0],
+            y: top as u16 + 1,
+        }
+    }
+
+    /// How far along y one step along an axis after the first goes: the height of a layer of the
+    /// axis before, with the holes that pad it.
+    pub fn stride(&self, axis: usize) -> u16 {
+        self.wide_stride(axis).expect("shapes fit in a coordinate") as u16
+    }
+
+    fn wide_stride(&self, axis: usize) -> Option<u32> {
+        debug_assert!((1..self.axes()).contains(&axis));
+        // Each layer is padded to twice its height less one, so that components up to the height
+        // less one either way read the same way in any delta.
+        (1..axis).try_fold(1u32, |stride, i| {
+            stride.checked_mul(self.extents[i] as u32 * 2 - 1)
+        })
+    }
+
+    /// The cell at a point given by its position along each axis, or None if it's off the board.
+    pub fn coord(&self, at: &[u16]) -> Option<Coord> {
+        if at.len() != self.axes() || at.iter().zip(self.extents()).any(|(&a, &n)| a >= n) {
+            return None;
+        }
+        let y: u32 = (1..self.axes())
+            .map(|i| at[i] as u32 * self.stride(i) as u32)
+            .sum();
+        Some(Coord {
+            x: at[0],
+            y: y as u16,
+        })
+    }
+
+    /// The position along each axis of a cell, or None if it's off the board or in the padding
+    /// between layers.
+    pub fn point(&self, c: Coord) -> Option<SmallVec<[u16; 4]>> {
+        let mut y = c.y as u32;
+        let mut at = SmallVec::from_slice(&[c.x]);
+        for i in 1..self.axes() {
+            let n = self.extents[i] as u32;
+            let a = if i + 1 == self.axes() {
+                y
+            } else {
+                let a = y % (n * 2 - 1);
+                y /= n * 2 - 1;
+                a
+            };
+            if a >= n {
+                return None;
+            }
+            at.push(a as u16);
+        }
+        Some(at).filter(|_| c.x < self.extents[0])
+    }
+
+    /// A delta's component along each axis, then zeroes.
+    pub(crate) fn split(&self, d: Delta) -> [i32; Shape::MAX_AXES] {
+        let mut parts = [0; Shape::MAX_AXES];
+        parts[0] = d.dx();
+        let mut dy = d.dy();
+        let last = self.axes() - 1;
+        for (i, part) in parts.iter_mut().enumerate().take(last + 1).skip(1) {
+            if i == last {
+                *part = dy;
+                break;
+            }
+            // Components are read balanced, between half the padded layer either way.
+            let base = self.extents[i] as i32 * 2 - 1;
+            *part = dy.rem_euclid(base);
+            if *part > base / 2 {
+                *part -= base;
+            }
+            dy = (dy - *part) / base;
+        }
+        parts
+    }
+
+    /// The delta with the given component along each axis.
+    pub(crate) fn join(&self, parts: &[i32]) -> Delta {
+        let dy = (1..self.axes())
+            .map(|i| parts[i] * self.stride(i) as i32)
+            .sum();
+        Delta::new(parts[0], dy)
+    }
+}
+
+impl std::convert::TryFrom<Vec<u16>> for Shape {
+    type Error = ShapeError;
+
+    fn try_from(extents: Vec<u16>) -> Result<Shape, ShapeError> {
+        Shape::new(&extents)
+    }
+}
+
+impl From<Shape> for Vec<u16> {
+    fn from(shape: Shape) -> Vec<u16> {
+        shape.extents().to_vec()
+    }
+}
+
+impl core::fmt::Display for Shape {
+    /// The extents, as in `3x3x3`.
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        for (i, n) in self.extents().iter().enumerate() {
+            if i != 0 {
+                write!(f, "x")?;
+            }
+            write!(f, "{}", n)?;
+        }
+        Ok(())
+    }
+}
+
+impl Tiling {
+    /// Every delta on a hyper board that goes no more than `radius` along any axis, bar zero; on
+    /// other tilings, nothing.
+    ///
+    /// The movement rules pick their directions out of these on a hyper board, which has too many
+    /// to list. Axes shorter than the radius are only tried as far as they go.
+    pub(crate) fn neighbours(self, radius: u16) -> impl Iterator<Item = Delta> + Clone {
+        let shape = match self {
+            Tiling::Hyper(shape) => Some(shape),
+            Tiling::Square | Tiling::Hex => None,
+        };
+        let reach = move |shape: Shape, i: usize| radius.min(shape.extents[i] - 1) as usize;
+        let count = shape.map_or(0, |shape| {
+            (0..shape.axes()).map(|i| reach(shape, i) * 2 + 1).product()
+        });
+        (0..count).filter_map(move |mut n| {
+            let shape = shape?;
+            let mut parts = [0; Shape::MAX_AXES];
+            for (i, part) in parts.iter_mut().enumerate().take(shape.axes()) {
+                let r = reach(shape, i);
+                *part = (n % (r * 2 + 1)) as i32 - r as i32;
+                n /= r * 2 + 1;
+            }
+            Some(shape.join(&parts)).filter(|&d| d != Delta::ZERO)
+        })
+    }
+}
+
+impl Board {
+    /// An empty hyper board of the given shape, with an automaton in the middle (rounding down).
+    pub fn hyper(extents: &[u16]) -> Result<Board, ShapeError> {
+        let shape = Shape::new(extents)?;
+        let size = shape.size();
+        let particles = Grid::from_shape_fn(size.ix(), |(x, y)| {
+            let at = Coord {
+                x: x as u16,
+                y: y as u16,
+            };
+            match shape.point(at) {
+                Some(_) => Cell::new(Particle::Vacuum),
+                None => Cell::new(Particle::Vacuum).with_terrain(Terrain::Hole),
+            }
+        });
+        let mut board = Board {
+            particles,
+            size,
+            automatons: SmallVec::new(),
+            conflict_list: SmallVec::new(),
+            passable_list: SmallVec::new(),
+            topology: Topology::Flat,
+            tiling: Tiling::Hyper(shape),
+            index: LineIndex::default(),
+            hash: 0,
+        };
+        board.rebuild_lists();
+        let middle: SmallVec<[u16; 4]> = extents.iter().map(|n| n / 2).collect();
+        board.place(
+            shape.coord(&middle).expect("the middle is on the board"),
+            Particle::Automaton,
+        );
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::mv;
+    use crate::*;
+
+    /// A game on an empty hyper board, and a way to name its cells.
+    fn empty_game(extents: &[u16]) -> (Game, impl Fn(&[u16]) -> Coord) {
+        let board = Board::hyper(extents).unwrap();
+        let shape = match board.tiling {
+            Tiling::Hyper(shape) => shape,
+            _ => unreachable!(),
+        };
+        let game = Game::new(board, 2, RuleSet::default()).unwrap();
+        (game, move |at: &[u16]| shape.coord(at).unwrap())
+    }
+
+    #[test]
+    fn shapes() {
+        let shape = Shape::new(&[5, 3, 4]).unwrap();
+        assert_eq!(shape.size(), Coord { x: 5, y: 18 });
+        assert_eq!((shape.stride(1), shape.stride(2)), (1, 5));
+        let c = shape.coord(&[4, 2, 3]).unwrap();
+        assert_eq!(c, Coord { x: 4, y: 17 });
+        assert_eq!(shape.point(c).unwrap().as_slice(), &[4, 2, 3]);
+        assert_eq!(shape.point(Coord { x: 4, y: 3 }), None);
+        assert_eq!(shape.coord(&[4, 3, 0]), None);
+
+        // Every delta between two cells reads back as the difference along each axis.
+        let d = shape.coord(&[0, 2, 1]).unwrap() - shape.coord(&[3, 0, 3]).unwrap();
+        assert_eq!(&shape.split(d)[..3], &[-3, 2, -2]);
+        assert_eq!(Tiling::Hyper(shape).axis_of(d), None);
+        let d = shape.coord(&[1, 0, 0]).unwrap() - shape.coord(&[1, 0, 3]).unwrap();
+        assert_eq!(Tiling::Hyper(shape).axis_of(d), Some(2));
+
+        assert_eq!(Shape::new(&[5, 5]), Err(ShapeError::TooFewAxes));
+        assert_eq!(Shape::new(&[5, 1, 5]), Err(ShapeError::TooShort(1)));
+        assert_eq!(Shape::new(&[2, 300, 300]), Err(ShapeError::TooBig));
+        assert_eq!(Shape::new(&[2; 12]), Err(ShapeError::TooBig));
+        assert!(Shape::new(&[2; 11]).is_ok());
+    }
+
+    #[test]
+    fn hyper_board() {
+        let board = Board::hyper(&[3, 3, 3]).unwrap();
+        assert_eq!(board.automatons.as_slice(), &[Coord { x: 1, y: 6 }]);
+        assert_eq!(board.to_notation(), "3x3x3:3__3__3/3__1@1__3/3__3__3");
+        assert_eq!(
+            Board::from_notation(&board.to_notation()),
+            Ok(board.clone())
+        );
+        assert!(!board.inbounds(Coord { x: 0, y: 3 }));
+        assert_eq!(
+            board.goal_sites(),
+            [
+                Coord { x: 0, y: 0 },
+                Coord { x: 2, y: 0 },
+                Coord { x: 2, y: 12 },
+                Coord { x: 0, y: 12 }
+            ]
+        );
+
+        let mut torus = board.clone();
+        torus.topology = Topology::Torus;
+        assert!(torus
+            .validate()
+            .unwrap_err()
+            .data
+            .contains(&BoardError::HyperTorus));
+        let mut padded = board;
+        padded.set_terrain(Coord { x: 0, y: 3 }, Terrain::Floor);
+        assert!(padded
+            .validate()
+            .unwrap_err()
+            .data
+            .contains(&BoardError::Padding(Coord { x: 0, y: 3 })));
+    }
+
+    #[test]
+    fn third_axis() {
+        let (mut game, h) = empty_game(&[5, 5, 5]);
+        game.board.place(h(&[2, 2, 4]), Particle::Attractor);
+        game.board.place(h(&[2, 2, 0]), Particle::Repulsor);
+        game.board.place(h(&[0, 2, 2]), Particle::Attractor);
+
+        let why = game.explain_automaton();
+        assert_eq!(why.axes.len(), 3);
+        assert_eq!(why.choice, AxisChoice::Urgent(2));
+        assert_eq!(why.to, h(&[2, 2, 3]));
+        assert_eq!(why.axes[2].rays.0.hit, Some(h(&[2, 2, 4])));
+        assert_eq!(why.axes[2].rays.0.dist, 2);
+        assert!(why.to_string().contains("Went with axis 2"), "{}", why);
+
+        // Two attractors as far off along x and along axis 2 tie; the column rule picks axis 2.
+        game.board.place(h(&[2, 2, 4]), Particle::Vacuum);
+        game.board.place(h(&[2, 2, 0]), Particle::Attractor);
+        let why = game.explain_automaton();
+        assert_eq!(why.choice, AxisChoice::ColumnRule(2));
+        assert_eq!(why.to, h(&[2, 2, 1]));
+        game.rules.tie = TieRule::Freeze;
+        assert_eq!(game.explain_automaton().choice, AxisChoice::Freeze);
+
+        // A repulsor in the next layer up is out of sight along the column, which runs into the
+        // padding between the layers first.
+        let (mut game, h) = empty_game(&[3, 3, 3]);
+        game.board.place(h(&[1, 0, 2]), Particle::Repulsor);
+        let why = game.explain_automaton();
+        assert_eq!(why.axes[1].rays.0.hit, None);
+        assert_eq!(why.axes[1].rays.0.dist, 2);
+        assert_eq!(why.choice, AxisChoice::Neither);
+    }
+
+    #[test]
+    fn rook_moves() {
+        let (mut game, h) = empty_game(&[3, 3, 3, 3]);
+        game.board.place(h(&[0, 0, 0, 0]), Particle::Repulsor);
+        game.board.place(h(&[0, 0, 0, 1]), Particle::Attractor);
+        game.board.place(h(&[2, 2, 2, 2]), Particle::Attractor);
+
+        for &(from, to) in &[
+            (&[0, 0, 0, 0], &[0, 1, 0, 1]),
+            (&[0, 0, 0, 0], &[1, 0, 0, 1]),
+            (&[2, 2, 2, 2], &[2, 2, 1, 1]),
+        ] {
+            assert_eq!(
+                game.propose_move(mv(0, h(from), h(to))).0,
+                MoveFeedback::Disallowed(Movement::Rook)
+            );
+        }
+        assert_eq!(
+            game.propose_move(mv(0, h(&[0, 0, 0, 0]), h(&[0, 0, 0, 2])))
+                .0,
+            MoveFeedback::Committed
+        );
+        assert_eq!(
+            game.propose_move(mv(1, h(&[2, 2, 2, 2]), h(&[2, 2, 0, 2])))
+                .0,
+            MoveFeedback::Committed
+        );
+        let results = game.try_complete_round().unwrap();
+        assert_eq!(results[0].1, MoveResult::OccupiedAt(h(&[0, 0, 0, 1])));
+        assert_eq!(results[1].1, MoveResult::Applied);
+        assert_eq!(
+            game.board.particles[h(&[2, 2, 0, 2]).ix()].what(),
+            Particle::Attractor
+        );
+    }
+
+    #[test]
+    fn plays_a_game() {
+        let board = Board::hyper(&[5, 5, 5]).unwrap();
+        let h = match board.tiling {
+            Tiling::Hyper(shape) => move |at: &[u16]| shape.coord(at).unwrap(),
+            _ => unreachable!(),
+        };
+        let mut game = Game::with_stock_goals(board, 2, RuleSet::default()).unwrap();
+        game.board.place(h(&[2, 2, 4]), Particle::Attractor);
+        game.board.place(h(&[0, 0, 0]), Particle::Repulsor);
+        game.emit_events = true;
+        let mut record = GameRecord::new(&game);
+
+        // Right next to the automaton along axis 2, the attractor leaves it no room to move; a
+        // step further off, it draws the automaton after it.
+        game.propose_move(mv(0, h(&[2, 2, 4]), h(&[2, 2, 3])));
+        game.propose_move(mv(1, h(&[0, 0, 0]), h(&[0, 0, 1])));
+        game.try_complete_round().unwrap();
+        assert_eq!(game.board.automatons.as_slice(), &[h(&[2, 2, 2])]);
+        game.propose_move(mv(0, h(&[2, 2, 3]), h(&[2, 2, 4])));
+        game.propose_move(mv(1, h(&[0, 0, 1]), h(&[0, 0, 0])));
+        game.try_complete_round().unwrap();
+        assert_eq!(game.board.automatons.as_slice(), &[h(&[2, 2, 3])]);
+
+        for event in game.drain_events() {
+            record.observe(&event);
+        }
+        let text = record.to_string();
+        let replayed = GameRecord::parse(&text).unwrap().replay().unwrap();
+        assert_eq!(replayed.board, game.board, "{}", text);
+    }
+}
@@ -4,7 +4,7 @@
 //!
 //! General crate design notes:
 //!
-//! - Max board size is "small" (256x256) but easy to bump (Coord).
+//! - Max board size is 65535x65535 (Coord), with checked arithmetic right up to the edges.
 //! - Max player count is "small" (256) but easy to bump (Pid).
 //! - `SmallVec` is used to size everything to require zero allocations
 //!   during a standard four-goal, two-player game.
@@ -18,12 +18,45 @@ extern crate displaydoc;
 extern crate ndarray;
 extern crate smallvec;
 
+mod bitboard;
+mod construct;
+mod event;
+mod hex;
+mod hyper;
+mod movement;
+mod nearest;
+mod notation;
+mod record;
+mod resolve;
+mod roster;
+mod rules;
+#[cfg(feature = "serde")]
+mod serialize;
+mod setup;
 mod support;
+#[cfg(test)]
+mod testutil;
+mod zobrist;
 
+pub use bitboard::*;
+pub use construct::*;
+pub use event::*;
+pub use hex::*;
+pub use hyper::*;
+pub use movement::*;
+use nearest::LineIndex;
+pub use notation::NotationError;
+pub use record::*;
+use resolve::MoveGraph;
+pub use roster::*;
+pub use rules::*;
+#[cfg(feature = "serde")]
+pub use serialize::{FormatError, FORMAT_VERSION};
+pub use setup::*;
 pub use support::*;
 
 use spandoc::spandoc;
-use tracing::{error, info, instrument, trace};
+use tracing::{info, instrument, trace};
 
 use displaydoc::Display;
 use ndarray::{arr2, Array2 as Grid};
@@ -33,6 +66,7 @@ use std::iter::FromIterator;
 
 /// "x, y {}"
 #[derive(Debug, Display, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CoordFeedback {
     /// is OK
     Ok,
@@ -42,28 +76,40 @@ pub enum CoordFeedback {
     Oob,
     /// is the automaton, which is off-limits
     Automaton,
+    /// is a wall, which is off-limits
+    Wall,
 }
 
 /// "Your move {}."
 #[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MoveFeedback {
     /// is now pending waiting for the other player
     Committed,
+    /// replaced your earlier move, and is now pending waiting for the other player
+    Replaced,
     /// had some problems: {0}
     SeeCoords(CoordsFeedback),
     /// must have different source and destination squares
     MustMove,
-    /// must move the piece only along a row or column (like a chess Rook)
-    AxisAlignedOnly,
+    /// must move the piece like a chess {0}
+    Disallowed(Movement),
     /// cannot be performed while other players are resolving conflicts
     WaitYourTurn,
+    /// cannot be made until every player has claimed their corners
+    InSetup,
     /// doesn't matter once the game is over
     GameOver,
+    /// comes from a player who isn't in this game
+    UnknownPlayer,
 }
 
 /// Game status:
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RoundState {
+    /// is waiting for players to claim their corners
+    Setup,
     /// not yet started
     Fresh,
     /// has players waiting
@@ -74,29 +120,151 @@ pub enum RoundState {
     GameOver,
 }
 
+/// What can sit on a cell.
+///
+/// Besides the README's pieces, there are some experimental ones. How each affects the automaton
+/// is up to `Particle::force` and `Particle::pass`: per-axis forces are exactly as urgent as the
+/// full ones along their own axis and act as walls along any other, blockers are walls outright,
+/// and mirrors turn the automaton's gaze instead of stopping it.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Particle {
     Repulsor,
     Attractor,
     Automaton,
     Vacuum,
+    /// Stops the automaton's gaze without pushing or pulling.
+    Blocker,
+    /// An attractor that only pulls along its row.
+    RowAttractor,
+    /// An attractor that only pulls along its column.
+    ColumnAttractor,
+    /// A repulsor that only pushes along its row.
+    RowRepulsor,
+    /// A repulsor that only pushes along its column.
+    ColumnRepulsor,
+    /// A diagonal mirror, which turns a gaze along the row onto the column and vice versa, keeping
+    /// its sign: looking toward +x past it, the automaton sees what lies toward +y.
+    Mirror,
 }
 
-// TODO: this is 3 bytes when it could be 1 :/
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub struct Cell {
-    pub what: Particle,
-    pub conflict: bool,
-    pub passable: bool,
+/// One cell of the board, packed into a byte: the particle in the low four bits, then the conflict
+/// and passable marks, then the terrain in the top two.
+///
+/// Serialized as a struct with a field for each part (see the serialize module).
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "serialize::CellRepr", into = "serialize::CellRepr")
+)]
+pub struct Cell(u8);
+
+impl Cell {
+    const TERRAINS: [Terrain; 3] = [Terrain::Floor, Terrain::Wall, Terrain::Hole];
+
+    const WHAT: u8 = 0b0000_1111;
+    const CONFLICT: u8 = 0b0001_0000;
+    const PASSABLE: u8 = 0b0010_0000;
+    const TERRAIN_SHIFT: u32 = 6;
+
+    /// An unmarked floor cell holding a particle.
+    pub const fn new(what: Particle) -> Cell {
+        Cell(what as u8)
+    }
+
+    /// The same cell, over different terrain.
+    pub const fn with_terrain(self, terrain: Terrain) -> Cell {
+        Cell(self.0 & !(0b11 << Cell::TERRAIN_SHIFT) | (terrain as u8) << Cell::TERRAIN_SHIFT)
+    }
+
+    pub fn what(self) -> Particle {
+        Particle::ALL[(self.0 & Cell::WHAT) as usize]
+    }
+
+    pub fn set_what(&mut self, what: Particle) {
+        self.0 = self.0 & !Cell::WHAT | what as u8;
+    }
+
+    pub fn conflict(self) -> bool {
+        self.0 & Cell::CONFLICT != 0
+    }
+
+    pub fn set_conflict(&mut self, conflict: bool) {
+        self.set_flag(Cell::CONFLICT, conflict);
+    }
+
+    pub fn passable(self) -> bool {
+        self.0 & Cell::PASSABLE != 0
+    }
+
+    pub fn set_passable(&mut self, passable: bool) {
+        self.set_flag(Cell::PASSABLE, passable);
+    }
+
+    pub fn terrain(self) -> Terrain {
+        Cell::TERRAINS[(self.0 >> Cell::TERRAIN_SHIFT) as usize]
+    }
+
+    pub fn set_terrain(&mut self, terrain: Terrain) {
+        *self = self.with_terrain(terrain);
+    }
+
+    fn set_flag(&mut self, flag: u8, on: bool) {
+        if on {
+            self.0 |= flag;
+        } else {
+            self.0 &= !flag;
+        }
+    }
+}
+
+impl core::fmt::Debug for Cell {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Cell")
+            .field("what", &self.what())
+            .field("conflict", &self.conflict())
+            .field("passable", &self.passable())
+            .field("terrain", &self.terrain())
+            .finish()
+    }
+}
+
+/// What a cell is made of, underneath any particle.
+///
+/// Terrain never changes during a game. Only floor cells can hold particles.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Terrain {
+    /// Ordinary playable floor.
+    #[default]
+    Floor,
+    /// An immovable wall. Nothing can move from, onto or through it, and the automaton sees it as
+    /// the edge of the board.
+    Wall,
+    /// A hole in the board, which isn't part of the playable area at all. Holes are how arenas get
+    /// shapes other than rectangles.
+    Hole,
+}
+
+impl Terrain {
+    pub fn is_floor(&self) -> bool {
+        *self == Terrain::Floor
+    }
 }
 
 /// Player 0 move: {}
-#[derive(Debug, Display, PartialEq, Eq)]
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MoveResult {
     /// failed because there was never a piece to move at the source.
     NoSource,
     /// failed the move is occluded between source and destination by a piece at {0}.
     OccupiedAt(Coord),
+    /// failed because it is part of a cycle of moves, whose pieces all stay put.
+    Cycle,
+    /// failed because the piece that got to the source can't move that way.
+    Disallowed,
     /// applied!
     Applied,
 }
@@ -105,9 +273,37 @@ pub enum MoveResult {
 pub struct Board {
     pub particles: Grid<Cell>,
     pub size: Coord,
-    pub automaton_location: Coord,
-    pub conflict_list: SmallVec<[Coord; 16]>, // TODO: compare performance scanning this list to scanning the whole grid
+    /// Where the automatons are, ordered by x and then y. Most boards have exactly one.
+    pub automatons: SmallVec<[Coord; 1]>,
+    /// The marked cells, so clearing the marks needn't scan the grid. `benches/board.rs` compares
+    /// the two.
+    pub conflict_list: SmallVec<[Coord; 16]>,
     pub passable_list: SmallVec<[Coord; 16]>,
+    pub topology: Topology,
+    pub tiling: Tiling,
+    /// The cells along each row and column that stop the automaton's gaze, so it can see past
+    /// the empty floor between them.
+    pub(crate) index: LineIndex,
+    /// The Zobrist hash of the cells; see `Board::zobrist`.
+    pub(crate) hash: u64,
+}
+
+/// How the edges of a board join up.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Topology {
+    /// The edges are the end of the board.
+    #[default]
+    Flat,
+    /// Each edge joins the opposite one, so that moves and the automaton's gaze wrap around.
+    /// There are no corners, so goals go elsewhere (see `Board::goal_sites`).
+    Torus,
+}
+
+impl Topology {
+    pub fn is_flat(&self) -> bool {
+        *self == Topology::Flat
+    }
 }
 
 impl Board {
@@ -115,27 +311,36 @@ impl Board {
     ///
     /// This method considers it allowable to move the automaton, and is part of the call graph
     /// of Game::update_automaton.
-    pub(crate) fn do_move(&mut self, from: Coord, to: Coord) -> MoveResult {
+    pub(crate) fn do_move(
+        &mut self,
+        from: Coord,
+        to: Coord,
+        rule: &impl MovementRule,
+    ) -> MoveResult {
         use MoveResult::*;
 
         // debug_assert checks invariants that should be established by propose_move
         debug_assert!(self.inbounds(from) && self.inbounds(to));
-
-        let delta = to - from;
-        debug_assert!(!delta.is_zero());
-        debug_assert!(delta.is_axial());
+        debug_assert_ne!(from, to);
 
         let src = self.particles[from.ix()];
         let dst = self.particles[to.ix()];
 
-        debug_assert!(!src.what.is_vacuum());
+        debug_assert!(!src.what().is_vacuum());
 
-        debug_assert!(!src.conflict && !dst.conflict);
+        debug_assert!(!src.conflict() && !dst.conflict());
 
-        let axis = delta.axial_unit();
-        for offset in 1..=delta.displacement() {
-            let c = from + axis * offset as isize;
-            if self.particles[c.ix()].occludes() {
+        // The piece may not be the one the move was proposed for, if it arrived along a chain.
+        let delta = self.displacement(from, to);
+        if !rule.allows(delta, self.tiling) {
+            return Disallowed;
+        }
+        for offset in rule.path(delta, self.tiling) {
+            let c = match self.offset(from, offset) {
+                Some(c) => c,
+                None => return Disallowed,
+            };
+            if !self.inbounds(c) || self.particles[c.ix()].occludes() {
                 return OccupiedAt(c);
             }
         }
@@ -147,27 +352,36 @@ impl Board {
 }
 
 /// Decisions of the Automaton on one axis.
-#[derive(Debug, Clone)]
-enum AutomatonDecision {
+///
+/// `pos` is true for a move in the positive direction along the axis. Decisions are ordered (and
+/// compared for equality) by urgency alone, so two decisions pointing opposite ways can be equal.
+#[derive(Debug, Display, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AutomatonDecision {
+    /// toward the attractor {att_dist} away, from the repulsor {rep_dist} away
     UnbalancedPair {
         pos: bool,
         att_dist: usize,
         rep_dist: usize,
     },
-    FromRepulsor {
-        pos: bool,
-        rep_dist: usize,
-    },
-    TowardAttractor {
-        pos: bool,
-        att_dist: usize,
-    },
+    /// away from the repulsor {rep_dist} away
+    FromRepulsor { pos: bool, rep_dist: usize },
+    /// toward the attractor {att_dist} away
+    TowardAttractor { pos: bool, att_dist: usize },
+    /// nowhere
     None,
 }
 
 impl Ord for AutomatonDecision {
-    /// Which of these two automaton decisions is more urgent?
+    /// Which of these two automaton decisions is more urgent, under the canonical rules?
     fn cmp(&self, other: &AutomatonDecision) -> Ordering {
+        self.cmp_under(other, &RuleSet::default())
+    }
+}
+
+impl AutomatonDecision {
+    /// Which of these two automaton decisions is more urgent under the given rules?
+    pub fn cmp_under(&self, other: &AutomatonDecision, rules: &RuleSet) -> Ordering {
         use AutomatonDecision::*;
 
         // Yo, what's up with all these .reverse() calls?
@@ -175,8 +389,8 @@ impl Ord for AutomatonDecision {
         // Well, the README.md describes the rules in a particular way, and to
         // make the code easy to verify, the code is written that way too.
 
-        self.priority()
-            .cmp(&other.priority())
+        self.priority(rules)
+            .cmp(&other.priority(rules))
             .then_with(|| match (self, other) {
                 // same priority means same enum variant!
                 (
@@ -212,6 +426,174 @@ impl Ord for AutomatonDecision {
     }
 }
 
+/// Which axis the automaton went with:
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AxisChoice {
+    /// none, having nowhere to go
+    Neither,
+    /// axis {0}, which was the most urgent
+    Urgent(usize),
+    /// axis {0}, since the most urgent axes tied and the column rule picks the last of them
+    ColumnRule(usize),
+    /// none, since the most urgent axes tied and it freezes instead of applying the column rule
+    Freeze,
+}
+
+impl AxisChoice {
+    /// The axis moved along, if any.
+    pub fn axis(self) -> Option<usize> {
+        match self {
+            AxisChoice::Urgent(axis) | AxisChoice::ColumnRule(axis) => Some(axis),
+            AxisChoice::Neither | AxisChoice::Freeze => None,
+        }
+    }
+}
+
+/// What the automaton made of one axis.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisExplanation {
+    /// The nearest particles in the positive and negative directions.
+    pub rays: (Raycast, Raycast),
+    pub decision: AutomatonDecision,
+}
+
+/// Why the automaton would move where it does, as worked out by `Game::explain_automaton`.
+///
+/// On a flat board, axis 0 is the row (x) and axis 1 the column (y). A hex board adds axis 2, the
+/// diagonal along which x increases as y decreases, and a hyper board an axis for each of its
+/// further dimensions.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AutomatonExplanation {
+    pub from: Coord,
+    pub to: Coord,
+    /// The tiling of the board, which says what the axes are.
+    pub tiling: Tiling,
+    pub axes: SmallVec<[AxisExplanation; 2]>,
+    pub choice: AxisChoice,
+}
+
+impl core::fmt::Display for AutomatonExplanation {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let name = |i: usize| match (i, self.tiling) {
+            (0, _) => "the row".to_string(),
+            (1, _) => "the column".to_string(),
+            (_, Tiling::Hex) => "the diagonal".to_string(),
+            _ => format!("axis {}", i),
+        };
+        for (i, axis) in self.axes.iter().enumerate() {
+            write!(f, "Along {}: {}. ", name(i), axis.decision)?;
+        }
+        match self.choice {
+            AxisChoice::Urgent(i) => write!(f, "Went with {}, which was more urgent, ", name(i))?,
+            AxisChoice::ColumnRule(i) => write!(
+                f,
+                "Went with {}, since the axes tied and the column rule applies, ",
+                name(i)
+            )?,
+            AxisChoice::Neither => write!(f, "Went with neither, ")?,
+            AxisChoice::Freeze => write!(
+                f,
+                "Went with neither, since the axes tied and it freezes instead of applying the \
+                 column rule, "
+            )?,
+        }
+        if self.from == self.to {
+            write!(f, "staying at {}.", self.to)
+        } else {
+            write!(f, "moving from {} to {}.", self.from, self.to)
+        }
+    }
+}
+
+/// Decide where the automaton would go along one axis, given what it sees in the positive and
+/// negative directions (see `Raycast::sighting`).
+#[instrument]
+fn evaluate_axis(pos: Sighting, neg: Sighting) -> AutomatonDecision {
+    use AutomatonDecision::*;
+    use Force::{Attract as A, Nothing as V, Repel as R};
+
+    let (pos_dist, neg_dist) = (pos.dist, neg.dist);
+    // Whether the automaton has room to step in each direction.
+    let (pos_room, neg_room) = (pos.room, neg.room);
+
+    match (pos.force, neg.force) {
+        (A, R) if pos_room => UnbalancedPair {
+            pos: true,
+            att_dist: pos_dist,
+            rep_dist: neg_dist,
+        },
+        (R, A) if neg_room => UnbalancedPair {
+            pos: false,
+            att_dist: neg_dist,
+            rep_dist: pos_dist,
+        },
+        (R, R) if pos_dist > neg_dist && pos_room || pos_dist < neg_dist && neg_room => {
+            FromRepulsor {
+                pos: pos_dist > neg_dist,
+                rep_dist: std::cmp::min(pos_dist, neg_dist),
+            }
+        }
+        (R, V) if neg_room => FromRepulsor {
+            pos: false,
+            rep_dist: pos_dist,
+        },
+        (V, R) if pos_room => FromRepulsor {
+            pos: true,
+            rep_dist: neg_dist,
+        },
+        (A, A) if pos_dist < neg_dist && pos_room || pos_dist > neg_dist && neg_room => {
+            TowardAttractor {
+                pos: pos_dist < neg_dist,
+                att_dist: std::cmp::min(pos_dist, neg_dist),
+            }
+        }
+        (A, V) if pos_room => TowardAttractor {
+            pos: true,
+            att_dist: pos_dist,
+        },
+        (V, A) if neg_room => TowardAttractor {
+            pos: false,
+            att_dist: neg_dist,
+        },
+        _ => None,
+    }
+}
+
+/// Pick the axis the automaton moves along, from its decision on each.
+///
+/// The most urgent decision wins. If several axes tie for it, the column rule picks the last of
+/// them (on a flat board, the column); otherwise the automaton freezes.
+fn choose_axis(axes: &[AxisExplanation], rules: &RuleSet) -> AxisChoice {
+    let urgent = |i: usize, j: usize| axes[i].decision.cmp_under(&axes[j].decision, rules);
+    let best = match (0..axes.len()).max_by(|&i, &j| urgent(i, j)) {
+        Some(best) if axes[best].decision.direction().is_some() => best,
+        _ => return AxisChoice::Neither,
+    };
+    let mut tied = (0..axes.len()).filter(|&i| urgent(i, best) == Ordering::Equal);
+    match (tied.clone().count(), rules.tie) {
+        (1, _) => AxisChoice::Urgent(best),
+        (_, TieRule::Freeze) => {
+            info!("avoided applying the column rule");
+            AxisChoice::Freeze
+        }
+        (_, TieRule::ColumnRule) => AxisChoice::ColumnRule(tied.next_back().unwrap()),
+    }
+}
+
+/// A completed round, as kept in the game's history.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoundHistory {
+    /// The board as it was when the round started.
+    pub before: Board,
+    pub results: SmallVec<[(Move, MoveResult); 2]>,
+    pub automatons_from: SmallVec<[Coord; 1]>,
+    pub automatons_to: SmallVec<[Coord; 1]>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Game {
     pub winner: Option<Pid>,
@@ -223,28 +605,134 @@ pub struct Game {
     pub pending_moves: SmallVec<[Move; 2]>,
     /// Goal locations, when the automaton enters one of these that player wins.
     pub goals: SmallVec<[(Coord, Pid); 4]>,
-    pub player_count: u8,
-    pub use_column_rule: bool,
+    /// The registered players, one for each seat at the table (see `Game::player_count`).
+    pub roster: Roster,
+    pub rules: RuleSet,
+    /// Whether the game queues up events as it goes. Off unless set; see `Game::new`.
+    pub emit_events: bool,
+    /// Events not yet taken by drain_events, while emit_events is set. Nothing but draining empties
+    /// it, so whoever sets emit_events must drain it.
+    pub events: SmallVec<[GameEvent; 4]>,
+    /// Completed rounds, oldest first.
+    pub(crate) history: Vec<RoundHistory>,
+    /// Undone rounds, most recently undone last, with the game as it was after each.
+    pub(crate) undone: Vec<(RoundHistory, Board, Option<Pid>)>,
 }
 
 impl Game {
-    /// Create a new game using the given board.
-    pub fn new(board: Board, player_count: u8, use_column_rule: bool) -> Game {
-        Game {
+    /// Create a new game using the given board, which must be consistent (see `Board::validate`).
+    ///
+    /// The game doesn't queue events (see `GameEvent`) unless `emit_events` is set. Whoever sets
+    /// it must take them with `drain_events` after each call that changes the game, since nothing
+    /// else bounds the queue. Searches that clone games by the thousand should leave it off.
+    pub fn new(board: Board, player_count: u8, rules: RuleSet) -> Result<Game, BoardErrors> {
+        board.validate()?;
+        Ok(Game {
             winner: None,
             locked_players: SmallVec::new(),
             board,
             round: RoundState::Fresh,
             pending_moves: SmallVec::new(),
             goals: SmallVec::new(),
-            player_count,
-            use_column_rule,
+            roster: Roster::new(player_count),
+            rules,
+            emit_events: false,
+            events: SmallVec::new(),
+            history: Vec::new(),
+            undone: Vec::new(),
+        })
+    }
+
+    /// The rounds completed so far, oldest first.
+    pub fn history(&self) -> &[RoundHistory] {
+        &self.history
+    }
+
+    /// Take back the last completed round, returning false if there isn't one.
+    ///
+    /// Any moves pending in the current round are discarded.
+    #[instrument]
+    pub fn undo_round(&mut self) -> bool {
+        let last = match self.history.pop() {
+            Some(last) => last,
+            None => return false,
+        };
+        let after = std::mem::replace(&mut self.board, last.before.clone());
+        self.undone.push((last, after, self.winner));
+        self.winner = None;
+        self.reset_round();
+        self.emit(GameEvent::RoundUndone);
+        true
+    }
+
+    /// Put back the last round taken back by undo_round, returning false if there isn't one.
+    ///
+    /// Completing a round forgets any rounds that could have been redone. Any moves pending in the
+    /// current round are discarded.
+    #[instrument]
+    pub fn redo_round(&mut self) -> bool {
+        let (last, after, winner) = match self.undone.pop() {
+            Some(undone) => undone,
+            None => return false,
+        };
+        self.board = after;
+        self.history.push(last);
+        self.winner = winner;
+        self.reset_round();
+        self.emit(GameEvent::RoundRedone);
+        true
+    }
+
+    /// Forget the current round's moves and marks, starting it afresh (or ending the game).
+    fn reset_round(&mut self) {
+        self.pending_moves.clear();
+        self.locked_players.clear();
+        self.board.clear_marks();
+        self.round = if self.winner.is_some() {
+            RoundState::GameOver
+        } else {
+            RoundState::Fresh
+        };
+    }
+
+    /// How many players there are, which is however many are registered in the roster.
+    pub fn player_count(&self) -> u8 {
+        self.roster.len() as u8
+    }
+
+    /// Take all the events that happened since the last call.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = GameEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Queue up an event, if anyone's listening.
+    pub(crate) fn emit(&mut self, event: GameEvent) {
+        if self.emit_events {
+            self.events.push(event);
+        }
+    }
+
+    /// Why a player can't propose any move right now, if they can't.
+    pub(crate) fn barred(&self, who: Pid) -> Option<MoveFeedback> {
+        use MoveFeedback::*;
+        if !self.roster.contains(who) {
+            Some(UnknownPlayer)
+        } else if self.round == RoundState::GameOver {
+            Some(GameOver)
+        } else if self.round == RoundState::Setup {
+            Some(InSetup)
+        } else if self.locked_players.contains(&who) {
+            Some(WaitYourTurn)
+        } else {
+            None
         }
     }
 
     /// Propose a move, returning some feedback about it, and true if the state
     /// machine is ready to advance (try_complete_round preconditions are met).
     ///
+    /// A player has at most one pending move; proposing another replaces it.
+    ///
     /// Returns false if try_complete_round would panic.
     #[instrument]
     pub fn propose_move(&mut self, m: Move) -> (MoveFeedback, bool) {
@@ -256,42 +744,72 @@ impl Game {
 
         // rules for a single coord, returns false if we shouldn't continue
         fn consider(cfs: &mut CoordsFeedback, b: &Board, c: Coord) -> bool {
-            use CoordFeedback::*;
-            let feedback = if !b.inbounds(c) {
-                Oob
-            } else if b.is_automaton(c) {
-                Automaton
-            } else if b.is_conflict(c) {
-                Conflict
-            } else {
-                Ok
-            };
-            let res = feedback == Ok;
+            let feedback = b.coord_feedback(c);
+            let res = feedback == CoordFeedback::Ok;
             cfs.data.push((c, feedback));
             res
         }
 
-        let res = if self.round == RoundState::GameOver {
-            GameOver
-        } else if self.locked_players.contains(&m.who) {
-            WaitYourTurn //                XXX XXX XXX  ~~(v)~~ XXX XXX XXX
+        let res = if let Some(res) = self.barred(m.who) {
+            res //                         XXX XXX XXX  ~~(v)~~ XXX XXX XXX
         } else if !consider(&mut cfs, &self.board, m.from) | !consider(&mut cfs, &self.board, m.to)
         {
             //      load bearing non-short-circuiting  ~~~(^)~~~ to accumulate both coord results!
             SeeCoords(cfs)
         } else if m.from == m.to {
             MustMove
-        } else if !(m.from.x == m.to.x || m.from.y == m.to.y) {
-            AxisAlignedOnly
+        } else if let Some(movement) = self.disallowing(m.from, m.to) {
+            Disallowed(movement)
         } else {
             Committed
         };
 
-        if res == Committed {
+        let res = if res != Committed {
+            res
+        } else if let Some(pending) = self.pending_moves.iter_mut().find(|p| p.who == m.who) {
+            *pending = m;
+            Replaced
+        } else {
             self.pending_moves.push(m);
-        }
+            if self.round == RoundState::Fresh {
+                self.round = RoundState::PartiallySubmitted;
+            }
+            Committed
+        };
 
-        (res, self.pending_moves.len() == self.player_count as usize)
+        self.emit(match res {
+            Committed | Replaced => GameEvent::MoveAck {
+                m,
+                replaced: res == Replaced,
+            },
+            _ => GameEvent::MoveInvalid {
+                who: m.who,
+                feedback: res.clone(),
+            },
+        });
+
+        (
+            res,
+            self.pending_moves.len() == self.player_count() as usize,
+        )
+    }
+
+    /// Withdraw a player's pending move, returning it.
+    ///
+    /// Returns None if the player has no pending move, or if their move is locked in while other
+    /// players resolve conflicts.
+    #[instrument]
+    pub fn withdraw_move(&mut self, who: Pid) -> Option<Move> {
+        if self.locked_players.contains(&who) {
+            return None;
+        }
+        let ix = self.pending_moves.iter().position(|p| p.who == who)?;
+        let m = self.pending_moves.remove(ix);
+        if self.pending_moves.is_empty() && self.round == RoundState::PartiallySubmitted {
+            self.round = RoundState::Fresh;
+        }
+        self.emit(GameEvent::MoveWithdrawn { who });
+        Some(m)
     }
 
     /// Returns Ok with the list of applied move to apply, or else the list of
@@ -299,7 +817,7 @@ impl Game {
     #[spandoc]
     #[instrument]
     fn resolve_conflicts(&mut self) -> Result<SmallVec<[Move; 2]>, SmallVec<[Move; 2]>> {
-        debug_assert!(self.pending_moves.len() == self.player_count as usize);
+        debug_assert!(self.pending_moves.len() == self.player_count() as usize);
 
         let mut seen_pairs = SmallVec::<[(Coord, Coord); 2]>::new();
 
@@ -309,10 +827,13 @@ impl Game {
         let mut conflict_moves = SmallVec::<[Move; 2]>::new();
         let mut locked_moves = SmallVec::<[Move; 2]>::new();
 
+        // Conflicted squares, and whether they were conflicted as a source.
+        let mut conflicts = SmallVec::<[(Coord, bool); 2]>::new();
+
         for &m in &self.pending_moves {
             let mut conflict = false;
             let this_pair = (m.from, m.to);
-            if seen_pairs.contains(&this_pair) {
+            if self.rules.merge_identical_moves && seen_pairs.contains(&this_pair) {
                 // multiple players specifying the same move is OK!
                 continue;
             }
@@ -320,9 +841,12 @@ impl Game {
 
             // See if there's a source conflict...
             if seen_from.contains(&m.from) {
-                trace!("marking source conflict on {}", coord = m.from);
+                trace!("marking source conflict on {coord}", coord = m.from);
                 conflict_moves.push(m);
                 self.board.mark_conflict(m.from);
+                if !conflicts.contains(&(m.from, true)) {
+                    conflicts.push((m.from, true));
+                }
                 conflict = true;
             } else {
                 seen_from.push(m.from);
@@ -330,9 +854,12 @@ impl Game {
 
             // Or a dest conflict...
             if seen_to.contains(&m.to) {
-                trace!("marking dest conflict on {}", coord = m.to);
+                trace!("marking dest conflict on {coord}", coord = m.to);
                 conflict_moves.push(m);
                 self.board.mark_conflict(m.to);
+                if !conflicts.contains(&(m.to, false)) {
+                    conflicts.push((m.to, false));
+                }
                 conflict = true;
             } else {
                 seen_to.push(m.to);
@@ -342,13 +869,13 @@ impl Game {
                 conflict_moves.push(m);
                 // We conflicted with some previous move, pull them out of the
                 // locked list and into the conflict list.
-                locked_moves = SmallVec::from_iter(locked_moves.into_iter().filter_map(|p| {
+                locked_moves = SmallVec::from_iter(locked_moves.into_iter().filter(|p| {
                     if p.from == m.from || p.to == m.to {
-                        trace!("caused conflict with player {:?}", pid = p.who);
-                        conflict_moves.push(p);
-                        None
+                        trace!("caused conflict with player {pid:?}", pid = p.who);
+                        conflict_moves.push(*p);
+                        false
                     } else {
-                        Some(p)
+                        true
                     }
                 }));
             } else {
@@ -357,7 +884,17 @@ impl Game {
             }
         }
 
-        if conflict_moves.len() == 0 {
+        for (at, source) in conflicts {
+            let players = self
+                .pending_moves
+                .iter()
+                .filter(|p| if source { p.from == at } else { p.to == at })
+                .map(|p| p.who)
+                .collect();
+            self.emit(GameEvent::Conflict { at, players });
+        }
+
+        if conflict_moves.is_empty() {
             Ok(locked_moves)
         } else {
             Err(conflict_moves)
@@ -369,45 +906,30 @@ impl Game {
     #[spandoc]
     pub fn try_complete_round(&mut self) -> Result<SmallVec<[(Move, MoveResult); 2]>, ()> {
         match self.resolve_conflicts() {
-            Ok(mut moves_to_apply) => {
+            Ok(moves_to_apply) => {
+                let mut before = self.board.clone();
+                before.clear_marks();
+
                 // Lift the moved pieces off the board
 
                 for m in &moves_to_apply {
                     self.board.mark_passable(m.from);
                 }
 
-                let mut results = SmallVec::with_capacity(moves_to_apply.len());
-
-                while moves_to_apply.len() != 0 {
-                    let mut made_progress = false;
-                    // FIXME: does this terminate? how does the python even work? it appears to
-                    // depend critically on passable not mucking with the particle type
-                    moves_to_apply.retain(|m| {
-                        if self.board.is_vacuum(m.from) {
-                            true
-                        } else {
-                            results.push((*m, self.board.do_move(m.from, m.to)));
-                            made_progress = true;
-                            false
-                        }
+                let results =
+                    MoveGraph::new(&moves_to_apply).apply(&mut self.board, &self.rules.movement);
+                for (m, result) in &results {
+                    self.emit(GameEvent::DoMove {
+                        m: *m,
+                        result: result.clone(),
                     });
-
-                    if !made_progress {
-                        for m in moves_to_apply.drain(..) {
-                            results.push((m, MoveResult::NoSource))
-                        }
-                    }
                 }
 
+                let automatons_from = self.board.automatons.clone();
                 self.update_automaton();
 
-                match self
-                    .goals
-                    .iter()
-                    .copied()
-                    .find(|(c, _)| c == &self.board.automaton_location)
-                {
-                    Some((_, who)) => {
+                match self.check_win() {
+                    Some(who) => {
                         self.round = RoundState::GameOver;
                         self.winner = Some(who);
                     }
@@ -417,13 +939,29 @@ impl Game {
                     }
                 }
 
+                self.pending_moves.clear();
+                self.locked_players.clear();
+                self.emit(GameEvent::TurnOver {
+                    automatons_from: automatons_from.clone(),
+                    automatons_to: self.board.automatons.clone(),
+                    winner: self.winner,
+                });
+
+                self.undone.clear();
+                self.history.push(RoundHistory {
+                    before,
+                    results: results.clone(),
+                    automatons_from,
+                    automatons_to: self.board.automatons.clone(),
+                });
+
                 Ok(results)
             }
             Err(moves_conflicted) => {
                 self.round = RoundState::ResolvingConflict;
-                self.pending_moves
-                    .retain(|e| !moves_conflicted.contains(&e));
-                for m in &moves_conflicted {
+                self.pending_moves.retain(|e| !moves_conflicted.contains(e));
+                // Whoever wasn't involved in a conflict keeps their move.
+                for m in &self.pending_moves {
                     if !self.locked_players.contains(&m.who) {
                         self.locked_players.push(m.who);
                     }
@@ -433,87 +971,97 @@ impl Game {
         }
     }
 
-    /// Update the automaton, returning true if it moved
+    /// Update the automatons.
+    ///
+    /// They step one at a time, in the order they stood in beforehand, each seeing the board as
+    /// the ones before it left it. An automaton only ever steps onto a vacuum, so they can't
+    /// collide.
     pub fn update_automaton(&mut self) {
-        let new_location = self.automaton_move();
-        if new_location != self.board.automaton_location {
-            debug_assert_eq!(
-                self.board
-                    .do_move(self.board.automaton_location, new_location),
-                MoveResult::Applied
-            );
+        for from in self.board.automatons.clone() {
+            let to = self.explain_step(&self.board, from).to;
+            if to != from {
+                let res = self.board.do_move(from, to, &Rook);
+                debug_assert_eq!(res, MoveResult::Applied);
+            }
+            self.emit(GameEvent::AutomatonMove { from, to });
         }
     }
 
-    /// Calculate the coordinate to which the automaton would move right now.
-    #[spandoc]
-    #[instrument]
+    /// Calculate the coordinate to which the first automaton would move right now.
+    #[cfg(test)]
     fn automaton_move(&self) -> Coord {
-        #[instrument]
-        fn evaluate_axis(pos: &Raycast, neg: &Raycast) -> AutomatonDecision {
-            use AutomatonDecision::*;
-            use Particle::{Attractor as A, Repulsor as R, Vacuum as V};
-
-            match (pos.what, neg.what) {
-                (A, R) if pos.dist > 1 => UnbalancedPair {
-                    pos: true,
-                    att_dist: pos.dist,
-                    rep_dist: neg.dist,
-                },
-                (R, A) if neg.dist > 1 => UnbalancedPair {
-                    pos: false,
-                    att_dist: neg.dist,
-                    rep_dist: pos.dist,
-                },
-                (R, R) if pos.dist != neg.dist => FromRepulsor {
-                    pos: pos.dist > neg.dist,
-                    rep_dist: std::cmp::min(pos.dist, neg.dist),
-                },
-                (R, V) if neg.dist > 1 => FromRepulsor {
-                    pos: false,
-                    rep_dist: pos.dist,
-                },
-                (V, R) if pos.dist > 1 => FromRepulsor {
-                    pos: true,
-                    rep_dist: neg.dist,
-                },
-                (A, A) if pos.dist != neg.dist => TowardAttractor {
-                    pos: pos.dist < neg.dist,
-                    att_dist: std::cmp::min(pos.dist, neg.dist),
-                },
-                (A, V) if pos.dist > 1 => TowardAttractor {
-                    pos: true,
-                    att_dist: pos.dist,
-                },
-                (V, A) if neg.dist > 1 => TowardAttractor {
-                    pos: false,
-                    att_dist: neg.dist,
-                },
-                _ => None,
+        self.explain_automaton().to
+    }
+
+    /// Work out where the first automaton would move right now, and why.
+    ///
+    /// The first automaton is the only one on most boards, and always steps first.
+    pub fn explain_automaton(&self) -> AutomatonExplanation {
+        self.explain_step(&self.board, self.board.automatons[0])
+    }
+
+    /// Work out where each automaton would move right now, and why, in the order they'd step.
+    pub fn explain_automatons(&self) -> SmallVec<[AutomatonExplanation; 1]> {
+        self.step_automatons(&mut self.board.clone())
+    }
+
+    /// Step each automaton on the given board under this game's rules, as `update_automaton`
+    /// does, and say why each went where it did.
+    ///
+    /// The board needn't be this game's, nor a `Board` at all; simulations can use a `BitBoard`.
+    pub fn step_automatons(
+        &self,
+        board: &mut impl BoardBackend,
+    ) -> SmallVec<[AutomatonExplanation; 1]> {
+        let mut explanations = SmallVec::new();
+        for from in SmallVec::<[Coord; 1]>::from(board.automatons()) {
+            let why = self.explain_step(board, from);
+            if why.to != from {
+                let res = board.do_move(from, why.to, &Rook);
+                debug_assert_eq!(res, MoveResult::Applied);
             }
+            explanations.push(why);
         }
+        explanations
+    }
 
-        /// Find the nearest particles in the four directions.
-        let xp = self.board.raycast(self.board.automaton_location, Delta::XP);
-        let xn = self.board.raycast(self.board.automaton_location, Delta::XN);
-        let yp = self.board.raycast(self.board.automaton_location, Delta::YP);
-        let yn = self.board.raycast(self.board.automaton_location, Delta::YN);
+    /// Work out where the automaton at a coordinate would move on the given board, and why.
+    #[spandoc]
+    #[instrument]
+    pub(crate) fn explain_step(
+        &self,
+        board: &impl BoardBackend,
+        from: Coord,
+    ) -> AutomatonExplanation {
+        let tiling = board.tiling();
 
-        let x_decision = evaluate_axis(&xp, &xn);
-        let y_decision = evaluate_axis(&yp, &yn);
+        /// Find the nearest particles in both directions along each axis.
+        let axes: SmallVec<[AxisExplanation; 2]> = tiling
+            .axes()
+            .map(|axis| {
+                let pos = board.raycast(from, axis);
+                let neg = board.raycast(from, axis * -1);
+                AxisExplanation {
+                    decision: evaluate_axis(pos.sighting(), neg.sighting()),
+                    rays: (pos, neg),
+                }
+            })
+            .collect();
 
-        let offset = if x_decision > y_decision {
-            x_decision.delta(Delta::XP)
-        } else {
-            // If the options are equally preferable, don't move unless we're using the column rule.
-            if !self.use_column_rule && x_decision == y_decision {
-                info!("avoided applying the column rule");
-                Delta::ZERO
-            } else {
-                y_decision.delta(Delta::YP)
-            }
+        let choice = choose_axis(&axes, &self.rules);
+        let offset = match choice.axis() {
+            Some(i) => axes[i].decision.delta(tiling.axis(i)),
+            None => Delta::ZERO,
         };
 
-        self.board.automaton_location + offset
+        AutomatonExplanation {
+            from,
+            to: board
+                .offset(from, offset)
+                .expect("the automaton only steps where it has room"),
+            tiling,
+            axes,
+            choice,
+        }
     }
 }
@@ -2,50 +2,66 @@ extern crate automatafl;
 use automatafl::*;
 
 fn main() {
-    let mut game = Game::new(Board::stock_two_player(), 2, true);
+    // The game record is written here when the session ends, or printed if there's no path.
+    let record_path = std::env::args().nth(1);
+
+    let mut game =
+        Game::with_stock_goals(Board::stock_two_player(), 2, RuleSet::default()).unwrap();
+    game.emit_events = true;
+    let mut record = GameRecord::new(&game);
     let mut line = String::new();
     let stdin = std::io::stdin();
-    while !game.winner.is_some() {
+    while game.winner.is_none() {
         println!("game state: {:?}", game.board);
 
         line.clear();
 
-        stdin.read_line(&mut line).expect("i/o why u fail :(");
+        if stdin.read_line(&mut line).expect("i/o why u fail :(") == 0 {
+            break;
+        }
 
-        let mut spl = line.split(' ');
+        let mut spl = line.split_whitespace();
 
-        let pid = spl.next().expect("need pid").parse::<u8>().unwrap();
-        let srcx = spl.next().expect("need srcx").parse::<u8>().unwrap();
-        let srcy = spl.next().expect("need srcy").parse::<u8>().unwrap();
-        let dstx = spl.next().expect("need dstx").parse::<u8>().unwrap();
-        let dsty = spl.next().expect("need dsty").parse::<u8>().unwrap();
+        let go = match line.trim() {
+            "undo" => {
+                game.undo_round();
+                false
+            }
+            "redo" => {
+                game.redo_round();
+                false
+            }
+            "why" => {
+                println!("{}", game.explain_automaton());
+                false
+            }
+            _ => {
+                let pid = spl.next().expect("need pid").parse::<u8>().unwrap();
+                let srcx = spl.next().expect("need srcx").parse::<u16>().unwrap();
+                let srcy = spl.next().expect("need srcy").parse::<u16>().unwrap();
+                let dstx = spl.next().expect("need dstx").parse::<u16>().unwrap();
+                let dsty = spl.next().expect("need dsty").parse::<u16>().unwrap();
 
-        let (fdb, go) = game.propose_move(Move {
-            who: Pid(pid),
-            from: Coord { x: srcx, y: srcy },
-            to: Coord { x: dstx, y: dsty },
-        });
-        println!("Move feedback: {}", fdb);
-        if go {
-            match game.try_complete_round() {
-                Ok(completed_moves) => {
-                    for (m, res) in completed_moves {
-                        println!("Player {}: {}", m.who.0, res)
-                    }
-                }
-                Err(()) => {
-                    print!(
-                        "Players locked: {}",
-                        game.locked_players
-                            .iter()
-                            .map(|p| p.0.to_string())
-                            .collect::<Vec<String>>()
-                            .join(", ")
-                    );
-                }
+                let (_, go) = game.propose_move(Move {
+                    who: Pid(pid),
+                    from: Coord { x: srcx, y: srcy },
+                    to: Coord { x: dstx, y: dsty },
+                });
+                go
             }
+        };
+        if go {
+            // Conflicts and results all show up as events.
+            let _ = game.try_complete_round();
+        }
+        for event in game.drain_events() {
+            println!("{}", event);
+            record.observe(&event);
         }
     }
 
-    println!("Player {} wins!", game.winner.unwrap().0);
+    match record_path {
+        Some(path) => std::fs::write(path, record.to_string()).expect("couldn't save the record"),
+        None => println!("{}", record),
+    }
 }
@@ -0,0 +1,606 @@
+//! How pieces move: rooks by default, or any of the chess-style rules the README suggests.
+//!
+//! Each rule is a `MovementRule`, which says which moves are allowed and which squares a move
+//! passes through. On a hex board the rules follow hexagonal chess: rooks move along the three
+//! axes, bishops along the six directions between them, and so on. On a hyper board they go along
+//! any axis, or any two axes for a bishop or a knight. The rule for each kind of
+//! particle is chosen by `RuleSet::movement`, both when a move is proposed and when it's carried
+//! out.
+
+use crate::*;
+use displaydoc::Display;
+use smallvec::{smallvec, SmallVec};
+
+/// How a piece may move, in terms of the delta from its source to its destination.
+///
+/// Working in deltas rather than coordinates lets the board decide which way round a move goes
+/// on a torus (see `Board::displacement`).
+pub trait MovementRule {
+    /// Whether a piece may move by a delta on a board of the given tiling, if nothing is in the
+    /// way.
+    fn allows(&self, delta: Delta, tiling: Tiling) -> bool;
+
+    /// The offsets from the source of the squares a move passes through on a board of the given
+    /// tiling, ending with the destination. Each must be free for the move to succeed.
+    ///
+    /// The default slides in a straight line, in the smallest steps that land on a square each
+    /// time, which suits any rule that only allows straight lines.
+    fn path(&self, delta: Delta, tiling: Tiling) -> SmallVec<[Delta; 8]> {
+        let steps = tiling.components(delta).fold(0, gcd).max(1);
+        let unit = Delta::new(delta.dx() / steps, delta.dy() / steps);
+        (1..=steps as isize).map(|i| unit * i).collect()
+    }
+}
+
+/// Any distance along a row or column. The canonical rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rook;
+
+/// Any distance along a diagonal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bishop;
+
+/// Two squares along one axis and one along the other, jumping over anything in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Knight;
+
+/// One square in any direction, diagonals included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct King;
+
+/// Anywhere on the board, so long as the destination is free.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Teleport;
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl MovementRule for Rook {
+    fn allows(&self, delta: Delta, tiling: Tiling) -> bool {
+        tiling.axis_of(delta).is_some() || delta == Delta::ZERO
+    }
+}
+
+impl MovementRule for Bishop {
+    /// On a hex board, the diagonals run between two neighbouring axes, crossing the edge where
+    /// two hexagons meet: (1, 1), (2, -1) and (1, -2), either way.
+    ///
+    /// On a hyper board, they run along any two axes at once, the same distance along each.
+    fn allows(&self, delta: Delta, tiling: Tiling) -> bool {
+        let (dx, dy) = (delta.dx() as i64, delta.dy() as i64);
+        match tiling {
+            Tiling::Square => dx.abs() == dy.abs(),
+            Tiling::Hex => dx == dy || dx == -2 * dy || dy == -2 * dx,
+            Tiling::Hyper(_) => {
+                let mut parts = tiling.components(delta);
+                match (parts.next(), parts.next(), parts.next()) {
+                    (None, _, _) => true,
+                    (Some(a), Some(b), None) => a == b,
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+impl MovementRule for Knight {
+    /// On a hex board, a knight moves to the twelve hexes three steps off that a rook can't reach.
+    /// On a hyper board, it moves two along one axis and one along another.
+    fn allows(&self, delta: Delta, tiling: Tiling) -> bool {
+        match tiling {
+            Tiling::Square => {
+                let (dx, dy) = (delta.dx().abs(), delta.dy().abs());
+                (dx, dy) == (1, 2) || (dx, dy) == (2, 1)
+            }
+            Tiling::Hex => tiling.distance(delta) == 3 && tiling.axis_of(delta).is_none(),
+            Tiling::Hyper(_) => {
+                let mut parts = tiling.components(delta);
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(a), Some(b), None) => (a.min(b), a.max(b)) == (1, 2),
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    fn path(&self, delta: Delta, _tiling: Tiling) -> SmallVec<[Delta; 8]> {
+        smallvec![delta]
+    }
+}
+
+impl MovementRule for King {
+    /// On a hyper board, a king moves one or none along each axis, as many axes at once as it
+    /// likes.
+    fn allows(&self, delta: Delta, tiling: Tiling) -> bool {
+        match tiling {
+            Tiling::Square => delta.dx().abs() <= 1 && delta.dy().abs() <= 1,
+            Tiling::Hex => {
+                let steps = tiling.distance(delta);
+                steps <= 1 || (steps == 2 && Bishop.allows(delta, tiling))
+            }
+            Tiling::Hyper(_) => tiling.components(delta).all(|p| p <= 1),
+        }
+    }
+}
+
+impl MovementRule for Teleport {
+    fn allows(&self, _delta: Delta, _tiling: Tiling) -> bool {
+        true
+    }
+
+    fn path(&self, delta: Delta, _tiling: Tiling) -> SmallVec<[Delta; 8]> {
+        smallvec![delta]
+    }
+}
+
+/// One of the stock movement rules, for choosing in a `RuleSet`.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Movement {
+    /// rook
+    Rook,
+    /// bishop
+    Bishop,
+    /// knight
+    Knight,
+    /// king
+    King,
+    /// teleport
+    Teleport,
+}
+
+/// The directions along a row or column, then the diagonals, on a square board.
+const SQUARE_LINES: [Delta; 8] = [
+    Delta { dx: 1, dy: 0 },
+    Delta { dx: -1, dy: 0 },
+    Delta { dx: 0, dy: 1 },
+    Delta { dx: 0, dy: -1 },
+    Delta { dx: 1, dy: 1 },
+    Delta { dx: -1, dy: -1 },
+    Delta { dx: 1, dy: -1 },
+    Delta { dx: -1, dy: 1 },
+];
+
+/// The directions along the three axes, then the diagonals between them, on a hex board.
+const HEX_LINES: [Delta; 12] = [
+    Delta { dx: 1, dy: 0 },
+    Delta { dx: -1, dy: 0 },
+    Delta { dx: 0, dy: 1 },
+    Delta { dx: 0, dy: -1 },
+    Delta { dx: 1, dy: -1 },
+    Delta { dx: -1, dy: 1 },
+    Delta { dx: 1, dy: 1 },
+    Delta { dx: -1, dy: -1 },
+    Delta { dx: 2, dy: -1 },
+    Delta { dx: -2, dy: 1 },
+    Delta { dx: 1, dy: -2 },
+    Delta { dx: -1, dy: 2 },
+];
+
+const SQUARE_KNIGHT: [Delta; 8] = [
+    Delta { dx: 1, dy: 2 },
+    Delta { dx: 2, dy: 1 },
+    Delta { dx: 2, dy: -1 },
+    Delta { dx: 1, dy: -2 },
+    Delta { dx: -1, dy: -2 },
+    Delta { dx: -2, dy: -1 },
+    Delta { dx: -2, dy: 1 },
+    Delta { dx: -1, dy: 2 },
+];
+
+const HEX_KNIGHT: [Delta; 12] = [
+    Delta { dx: 3, dy: -1 },
+    Delta { dx: 3, dy: -2 },
+    Delta { dx: 2, dy: 1 },
+    Delta { dx: 1, dy: 2 },
+    Delta { dx: -1, dy: 3 },
+    Delta { dx: -2, dy: 3 },
+    Delta { dx: -3, dy: 1 },
+    Delta { dx: -3, dy: 2 },
+    Delta { dx: -2, dy: -1 },
+    Delta { dx: -1, dy: -2 },
+    Delta { dx: 1, dy: -3 },
+    Delta { dx: 2, dy: -3 },
+];
+
+impl Movement {
+    /// The directions a piece moves in under this rule, and how many steps it may take in each:
+    /// as many as it likes for a rook or a bishop, and one for a king or a knight. None for a
+    /// teleport, which can go anywhere.
+    ///
+    /// A hyper board has too many directions to list, so there they're picked out of the nearby
+    /// deltas (see `Tiling::neighbours`) instead.
+    fn reach(self, tiling: Tiling) -> Option<(impl Iterator<Item = Delta> + Clone, usize)> {
+        let (lines, knight): (&'static [Delta], &'static [Delta]) = match tiling {
+            Tiling::Square => (&SQUARE_LINES, &SQUARE_KNIGHT),
+            Tiling::Hex => (&HEX_LINES, &HEX_KNIGHT),
+            Tiling::Hyper(_) => (&[], &[]),
+        };
+        let axial = std::cmp::min(tiling.axes().len() * 2, lines.len());
+        let (listed, most) = match self {
+            Movement::Rook => (&lines[..axial], usize::MAX),
+            Movement::Bishop => (&lines[axial..], usize::MAX),
+            Movement::Knight => (knight, 1),
+            Movement::King => (lines, 1),
+            Movement::Teleport => return None,
+        };
+        let radius = if self == Movement::Knight { 2 } else { 1 };
+        let found = tiling
+            .neighbours(radius)
+            .filter(move |&d| self.allows(d, tiling));
+        Some((listed.iter().copied().chain(found), most))
+    }
+
+    fn rule(self) -> &'static dyn MovementRule {
+        match self {
+            Movement::Rook => &Rook,
+            Movement::Bishop => &Bishop,
+            Movement::Knight => &Knight,
+            Movement::King => &King,
+            Movement::Teleport => &Teleport,
+        }
+    }
+}
+
+impl MovementRule for Movement {
+    fn allows(&self, delta: Delta, tiling: Tiling) -> bool {
+        self.rule().allows(delta, tiling)
+    }
+
+    fn path(&self, delta: Delta, tiling: Tiling) -> SmallVec<[Delta; 8]> {
+        self.rule().path(delta, tiling)
+    }
+}
+
+impl std::str::FromStr for Movement {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Movement, ()> {
+        Ok(match s {
+            "rook" => Movement::Rook,
+            "bishop" => Movement::Bishop,
+            "knight" => Movement::Knight,
+            "king" => Movement::King,
+            "teleport" => Movement::Teleport,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// The movement rule for each kind of particle players can move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PieceMovement {
+    pub attractor: Movement,
+    pub repulsor: Movement,
+}
+
+impl Default for PieceMovement {
+    fn default() -> PieceMovement {
+        PieceMovement {
+            attractor: Movement::Rook,
+            repulsor: Movement::Rook,
+        }
+    }
+}
+
+impl PieceMovement {
+    /// How a particle moves. Per-axis forces move like their full counterparts; automatons,
+    /// blockers and mirrors move like rooks.
+    pub fn of(&self, what: Particle) -> Movement {
+        use Particle::*;
+        match what {
+            Attractor | RowAttractor | ColumnAttractor => self.attractor,
+            Repulsor | RowRepulsor | ColumnRepulsor => self.repulsor,
+            Automaton | Vacuum | Blocker | Mirror => Movement::Rook,
+        }
+    }
+
+    /// Whether some particle players can move could make this move.
+    ///
+    /// This is what a move from a vacuum is checked against when it's proposed, since a piece of
+    /// any kind might arrive there earlier in the round.
+    pub fn any_allows(&self, delta: Delta, tiling: Tiling) -> bool {
+        self.attractor.allows(delta, tiling)
+            || self.repulsor.allows(delta, tiling)
+            || Rook.allows(delta, tiling)
+    }
+}
+
+impl Game {
+    /// The movement rule a proposed move breaks, if any.
+    ///
+    /// A move from a vacuum is allowed if any piece could make it. If none could, the attractor's
+    /// rule is the one reported.
+    pub(crate) fn disallowing(&self, from: Coord, to: Coord) -> Option<Movement> {
+        let movement = &self.rules.movement;
+        let (delta, tiling) = (self.board.displacement(from, to), self.board.tiling);
+        match self.board.particles[from.ix()].what() {
+            Particle::Vacuum if movement.any_allows(delta, tiling) => None,
+            Particle::Vacuum => Some(movement.attractor),
+            what => Some(movement.of(what)).filter(|m| !m.allows(delta, tiling)),
+        }
+    }
+
+    /// Every move from a piece that a player could propose right now and have accepted, in order
+    /// of the piece's coordinate.
+    ///
+    /// As with `propose_move`, nothing in the way is checked for, since it might move first. Moves
+    /// from a vacuum, which `propose_move` also accepts in case a piece arrives there along a
+    /// chain, aren't included.
+    ///
+    /// Only the squares each piece's rule can reach are looked at: rooks and bishops go along each
+    /// of their lines to the edge, and kings and knights try each of their few steps. So this costs
+    /// time in proportion to the moves it finds (on a hyper board, plus a look at each nearby delta
+    /// for each piece), and since nothing is allocated it's fine to call often, from a search or to
+    /// highlight where a piece can go.
+    pub fn legal_moves(&self, who: Pid) -> impl Iterator<Item = Move> + '_ {
+        let board = &self.board;
+        let open = move |c: Coord| board.coord_feedback(c) == CoordFeedback::Ok;
+        let cells = move || {
+            (0..board.size.x).flat_map(move |x| (0..board.size.y).map(move |y| Coord { x, y }))
+        };
+        let barred = self.barred(who).is_some();
+        cells()
+            .filter(move |&from| !barred && open(from) && !board.is_vacuum(from))
+            .flat_map(move |from| {
+                let what = board.particles[from.ix()].what();
+                let movement = self.rules.movement.of(what);
+                let reach = movement.reach(board.tiling);
+                let teleport = reach.is_none();
+                let steps = reach
+                    .into_iter()
+                    .flat_map(|(directions, most)| directions.map(move |d| (d, most)))
+                    .flat_map(move |(d, most)| {
+                        (1..=most).map_while(move |k| {
+                            // On a torus, a line stops where the other way round becomes shorter,
+                            // since that's the way a move there would go. On a hyper board, it
+                            // stops where it would carry over into another axis.
+                            let d = d * k as isize;
+                            let to = board.offset(from, d)?;
+                            Some(to).filter(|&to| {
+                                board.displacement(from, to) == d
+                                    && movement.allows(d, board.tiling)
+                            })
+                        })
+                    });
+                let anywhere = teleport
+                    .then(cells)
+                    .into_iter()
+                    .flatten()
+                    .filter(move |&to| to != from);
+                steps
+                    .chain(anywhere)
+                    .filter(move |&to| open(to))
+                    .map(move |to| Move { who, from, to })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::{c, mv};
+    use crate::*;
+
+    #[test]
+    fn stock_rules() {
+        use Movement::*;
+        let o = c(2, 2);
+        let cases = [
+            (c(2, 0), [true, false, false, false, true]),
+            (c(4, 4), [false, true, false, false, true]),
+            (c(3, 0), [false, false, true, false, true]),
+            (c(3, 3), [false, true, false, true, true]),
+            (c(2, 3), [true, false, false, true, true]),
+            (c(6, 3), [false, false, false, false, true]),
+        ];
+        for &(to, allowed) in &cases {
+            for (rule, &ok) in [Rook, Bishop, Knight, King, Teleport].iter().zip(&allowed) {
+                assert_eq!(
+                    rule.allows(to - o, Tiling::Square),
+                    ok,
+                    "{} to {}",
+                    rule,
+                    to
+                );
+            }
+        }
+        let d = Delta::new;
+        let square = Tiling::Square;
+        assert_eq!(
+            Bishop.path(d(-2, 2), square).as_slice(),
+            &[d(-1, 1), d(-2, 2)]
+        );
+        assert_eq!(Knight.path(d(1, -2), square).as_slice(), &[d(1, -2)]);
+        assert_eq!("knight".parse(), Ok(Knight));
+    }
+
+    #[test]
+    fn hex_rules() {
+        use Movement::*;
+        let d = Delta::new;
+        let cases = [
+            (d(2, -2), [true, false, false, false]),
+            (d(1, 1), [false, true, false, true]),
+            (d(4, -2), [false, true, false, false]),
+            (d(1, -1), [true, false, false, true]),
+            (d(2, 1), [false, false, true, false]),
+            (d(3, -1), [false, false, true, false]),
+        ];
+        for &(delta, allowed) in &cases {
+            for (rule, &ok) in [Rook, Bishop, Knight, King].iter().zip(&allowed) {
+                assert_eq!(
+                    rule.allows(delta, Tiling::Hex),
+                    ok,
+                    "{} by {:?}",
+                    rule,
+                    delta
+                );
+            }
+        }
+        let hex = Tiling::Hex;
+        assert_eq!(Bishop.path(d(4, -2), hex).as_slice(), &[d(2, -1), d(4, -2)]);
+        assert_eq!(Rook.path(d(-2, 2), hex).as_slice(), &[d(-1, 1), d(-2, 2)]);
+    }
+
+    #[test]
+    fn bishops_and_knights() {
+        let mut game = Game::new(
+            Board::stock_testing_empty(),
+            2,
+            RuleSet {
+                movement: PieceMovement {
+                    attractor: Movement::Bishop,
+                    repulsor: Movement::Knight,
+                },
+                ..RuleSet::default()
+            },
+        )
+        .unwrap();
+        game.board.place(c(0, 0), Particle::Attractor);
+        game.board.place(c(4, 0), Particle::Repulsor);
+        game.board.place(c(3, 1), Particle::Repulsor);
+
+        assert_eq!(
+            game.propose_move(mv(0, c(0, 0), c(0, 2))).0,
+            MoveFeedback::Disallowed(Movement::Bishop)
+        );
+        // From an empty square, any piece's rule will do; the one that arrives must follow its own.
+        assert_eq!(
+            game.propose_move(mv(0, c(1, 1), c(3, 3))).0,
+            MoveFeedback::Committed
+        );
+        assert_eq!(
+            game.propose_move(mv(0, c(0, 0), c(1, 1))).0,
+            MoveFeedback::Replaced
+        );
+        // The knight jumps the repulsor in its way.
+        assert_eq!(
+            game.propose_move(mv(1, c(4, 0), c(2, 1))).0,
+            MoveFeedback::Committed
+        );
+        game.try_complete_round().unwrap();
+        assert_eq!(
+            game.board.particles[c(1, 1).ix()].what(),
+            Particle::Attractor
+        );
+        assert_eq!(
+            game.board.particles[c(2, 1).ix()].what(),
+            Particle::Repulsor
+        );
+
+        // A chain hands the repulsor a bishop's move, which it can't make.
+        game.propose_move(mv(0, c(2, 1), c(3, 3)));
+        game.propose_move(mv(1, c(3, 3), c(4, 4)));
+        let results = game.try_complete_round().unwrap();
+        assert_eq!(results[0].1, MoveResult::Applied);
+        assert_eq!(results[1].1, MoveResult::Disallowed);
+        assert_eq!(
+            game.board.particles[c(3, 3).ix()].what(),
+            Particle::Repulsor
+        );
+    }
+
+    /// Check legal_moves against what propose_move accepts, for every move from a piece.
+    fn assert_legal_moves_match(game: &Game, who: u8) -> usize {
+        let legal: Vec<Move> = game.legal_moves(Pid(who)).collect();
+        let size = game.board.size;
+        let cells: Vec<Coord> = (0..size.x)
+            .flat_map(|x| (0..size.y).map(move |y| c(x, y)))
+            .collect();
+        for &from in &cells {
+            if game.board.particles[from.ix()].what().is_vacuum() {
+                continue;
+            }
+            for &to in &cells {
+                let m = mv(who, from, to);
+                let (res, _) = game.clone().propose_move(m);
+                let accepted = matches!(res, MoveFeedback::Committed | MoveFeedback::Replaced);
+                assert_eq!(legal.contains(&m), accepted, "{:?}: {:?}", m, res);
+            }
+        }
+        legal.len()
+    }
+
+    #[test]
+    fn legal_moves() {
+        let mut game = Game::new(Board::stock_testing(), 2, RuleSet::default()).unwrap();
+        // Eight pieces, each with four moves along its edge of the board and four across it, bar
+        // the middle ones' moves onto the automaton.
+        assert_eq!(assert_legal_moves_match(&game, 0), 8 * 8 - 4);
+        assert!(game
+            .legal_moves(Pid(1))
+            .all(|m| m.who == Pid(1) && m.from != m.to));
+
+        // Nothing moves from a conflict, or onto one.
+        game.board.mark_conflict(c(0, 2));
+        assert_eq!(assert_legal_moves_match(&game, 1), 60 - 7 - 3);
+        game.locked_players.push(Pid(0));
+        assert_eq!(game.legal_moves(Pid(0)).count(), 0);
+        assert_eq!(game.legal_moves(Pid(2)).count(), 0);
+
+        // Around a torus, lines go on past the edge until the other way round is shorter, and
+        // nothing stops them.
+        let mut game = Game::new(Board::stock_testing(), 2, RuleSet::default()).unwrap();
+        game.board.topology = Topology::Torus;
+        game.board.place(c(2, 0), Particle::Vacuum);
+        game.board.place(c(1, 3), Particle::Blocker);
+        game.board.set_terrain(c(3, 3), Terrain::Wall);
+        game.board.set_terrain(c(3, 1), Terrain::Hole);
+        assert_legal_moves_match(&game, 0);
+
+        for &(attractor, repulsor) in &[
+            (Movement::Bishop, Movement::Knight),
+            (Movement::King, Movement::Teleport),
+        ] {
+            let rules = RuleSet {
+                movement: PieceMovement {
+                    attractor,
+                    repulsor,
+                },
+                ..RuleSet::default()
+            };
+            let mut game = Game::new(Board::stock_testing(), 2, rules).unwrap();
+            assert_legal_moves_match(&game, 0);
+            game.board.topology = Topology::Torus;
+            assert_legal_moves_match(&game, 0);
+
+            let mut game = Game::new(Board::hex(2), 2, rules).unwrap();
+            game.board.place(c(2, 0), Particle::Attractor);
+            game.board.place(c(3, 1), Particle::Repulsor);
+            game.board.place(c(1, 3), Particle::Blocker);
+            assert_legal_moves_match(&game, 0);
+
+            assert_legal_moves_match(&hyper_game(rules), 0);
+        }
+
+        let mut game = Game::new(Board::hex(2), 2, RuleSet::default()).unwrap();
+        game.board.place(c(2, 0), Particle::Attractor);
+        game.board.place(c(3, 1), Particle::Repulsor);
+        assert_legal_moves_match(&game, 0);
+
+        // A rook in a corner of a hyper board can go two ways along each of its three axes. So can
+        // the blocker in the middle of the bottom layer, bar onto the automaton just above it.
+        let game = hyper_game(RuleSet::default());
+        assert_eq!(assert_legal_moves_match(&game, 0), 6 + 6 + 6 - 1);
+    }
+
+    /// A 3x3x3 hyper board with an attractor and a repulsor in two of its corners, and a blocker
+    /// in the middle of the bottom layer.
+    fn hyper_game(rules: RuleSet) -> Game {
+        let mut game = Game::new(Board::hyper(&[3, 3, 3]).unwrap(), 2, rules).unwrap();
+        let h = match game.board.tiling {
+            Tiling::Hyper(shape) => move |at: &[u16]| shape.coord(at).unwrap(),
+            _ => unreachable!(),
+        };
+        game.board.place(h(&[0, 0, 0]), Particle::Attractor);
+        game.board.place(h(&[2, 2, 2]), Particle::Repulsor);
+        game.board.place(h(&[1, 1, 0]), Particle::Blocker);
+        game
+    }
+}
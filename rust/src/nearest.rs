@@ -0,0 +1,205 @@
+//! An index of the cells along each row and column that stop a ray, kept up to date as the board
+//! changes, so the automaton can look straight past empty floor.
+//!
+//! Raycasting a cell at a time costs time in proportion to the width of the board. With the
+//! index, each hop along a row or column is a lookup in an ordered set, and keeping it up to date
+//! costs a few lookups for each cell that changes. Rays along a hex board's diagonal, or a hyper
+//! board's further axes, still go a cell at a time.
+
+use crate::*;
+use ndarray::Array2 as Grid;
+use std::collections::BTreeSet;
+
+/// The cells that stop a ray, being particles, walls or holes, in each row and column.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct LineIndex {
+    /// The x of each stop in each row, indexed by y.
+    rows: Vec<BTreeSet<u16>>,
+    /// The y of each stop in each column, indexed by x.
+    columns: Vec<BTreeSet<u16>>,
+}
+
+impl LineIndex {
+    pub(crate) fn new(particles: &Grid<Cell>) -> LineIndex {
+        let (w, h) = particles.dim();
+        let mut index = LineIndex {
+            rows: vec![BTreeSet::new(); h],
+            columns: vec![BTreeSet::new(); w],
+        };
+        for ((x, y), &cell) in particles.indexed_iter() {
+            let c = Coord {
+                x: x as u16,
+                y: y as u16,
+            };
+            index.update(c, cell);
+        }
+        index
+    }
+
+    /// Record whether a cell stops rays, after it changed.
+    pub(crate) fn update(&mut self, c: Coord, cell: Cell) {
+        let (x, y) = c.ix();
+        if !cell.what().is_vacuum() || cell.terrain() != Terrain::Floor {
+            self.rows[y].insert(c.x);
+            self.columns[x].insert(c.y);
+        } else {
+            self.rows[y].remove(&c.x);
+            self.columns[x].remove(&c.y);
+        }
+    }
+
+    /// How many cells of empty floor lie ahead of a ray from `from`, now at `co` and heading along
+    /// `d`, before the next step reaches a stop, `from`, or the edge. None if `d` isn't a step
+    /// along a row or column.
+    pub(crate) fn empty_run(
+        &self,
+        size: Coord,
+        topology: Topology,
+        co: Coord,
+        d: Delta,
+        from: Coord,
+    ) -> Option<usize> {
+        let (line, pos, len, from_pos) = match (d.dx(), d.dy()) {
+            (_, 0) => (&self.rows[co.y as usize], co.x, size.x, from.x),
+            (0, 1) | (0, -1) => (&self.columns[co.x as usize], co.y, size.y, from.y),
+            _ => return None,
+        };
+        let on_line = if d.dy() == 0 {
+            from.y == co.y
+        } else {
+            from.x == co.x
+        };
+        let (pos, len, forward) = (pos as usize, len as usize, d.dx() + d.dy() > 0);
+        let steps = steps_to_stop(
+            len,
+            pos,
+            forward,
+            topology,
+            Some(from_pos as usize).filter(|_| on_line),
+            |lo, hi| line.range(lo as u16..hi as u16).next().map(|&q| q as usize),
+            |lo, hi| {
+                line.range(lo as u16..hi as u16)
+                    .next_back()
+                    .map(|&q| q as usize)
+            },
+        );
+        Some(steps.unwrap_or(if forward { len - pos } else { pos + 1 }) - 1)
+    }
+}
+
+/// How many steps along a line of `len` cells it is from `pos` to the nearest stop, or to `from`
+/// if that's on the line, whichever comes first. On a flat board, None if the edge comes first.
+///
+/// The stops are found with `lowest` and `highest`, which give the lowest and highest stop in a
+/// range of positions.
+pub(crate) fn steps_to_stop(
+    len: usize,
+    pos: usize,
+    forward: bool,
+    topology: Topology,
+    from: Option<usize>,
+    lowest: impl Fn(usize, usize) -> Option<usize>,
+    highest: impl Fn(usize, usize) -> Option<usize>,
+) -> Option<usize> {
+    let torus = topology == Topology::Torus;
+    let found = if forward {
+        lowest(pos + 1, len)
+            .map(|q| q - pos)
+            .or_else(|| lowest(0, pos + 1).filter(|_| torus).map(|q| q + len - pos))
+    } else {
+        highest(0, pos)
+            .map(|q| pos - q)
+            .or_else(|| highest(pos, len).filter(|_| torus).map(|q| pos + len - q))
+    };
+
+    let to_from = from.and_then(|from| {
+        if torus {
+            let ahead = if forward {
+                from + len - pos
+            } else {
+                pos + len - from
+            };
+            Some((ahead - 1) % len + 1)
+        } else if forward && from > pos {
+            Some(from - pos)
+        } else if !forward && from < pos {
+            Some(pos - from)
+        } else {
+            None
+        }
+    });
+
+    match (found, to_from) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitboard::tests::{apply_edits, boards, edits};
+    use crate::*;
+    use proptest::prelude::*;
+
+    /// `Board::raycast` as it was before the index, a cell at a time.
+    fn stepwise(board: &Board, from: Coord, axis: Delta) -> Raycast {
+        let (mut co, mut d, mut clear) = (from, axis, None);
+        for dist in 1.. {
+            let next = board
+                .offset(co, d)
+                .filter(|&c| board.inbounds(c) && !board.is_wall(c));
+            co = match next {
+                Some(c) => c,
+                None => {
+                    return Raycast {
+                        what: Particle::Vacuum,
+                        hit: None,
+                        dist,
+                        axis: if d.dx() != 0 { 0 } else { 1 },
+                        clear: clear.unwrap_or(dist - 1),
+                    }
+                }
+            };
+            let what = if co == from {
+                Particle::Automaton
+            } else {
+                board.particles[co.ix()].what()
+            };
+            if !what.is_vacuum() && clear.is_none() {
+                clear = Some(dist - 1);
+            }
+            let axis = if d.dx() != 0 { 0 } else { 1 };
+            match what.pass(axis) {
+                Some(0) if d.dx() == 0 => d = Delta::new(d.dy(), 0),
+                Some(1) if d.dy() == 0 => d = Delta::new(0, d.dx()),
+                Some(_) => {}
+                None => {
+                    return Raycast {
+                        what,
+                        hit: Some(co),
+                        dist,
+                        axis,
+                        clear: clear.unwrap_or(dist - 1),
+                    }
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn kept_up_to_date(board in boards(), edits in edits()) {
+            apply_edits(board, edits, |board, at| {
+                prop_assert_eq!(&board.index, &LineIndex::new(&board.particles));
+                for &(dx, dy) in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                    let axis = Delta::new(dx, dy);
+                    prop_assert_eq!(board.raycast(at, axis), stepwise(board, at, axis));
+                }
+                Ok(())
+            })?;
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! Compact text notation for board positions, in the spirit of chess's FEN.
+//!
+//! A position is written as `WxH:` followed by W groups separated by `/`, one for each x
+//! coordinate (the same way round as the `arr2` literals of the stock boards), each listing the
+//! cells from y = 0 upward. The size is followed by `h` for a hex board and then `~` for a torus,
+//! as in `5x5h~:`. A hyper board's size lists its extent along every axis instead, as in `3x3x3:`,
+//! and its groups list the cells of its folded grid, padding and all (see `Board::hyper`). The
+//! cells are written:
+//!
+//! - `R` is a repulsor, `A` an attractor, `@` the automaton and `.` a vacuum.
+//! - The experimental particles are `#` for a blocker and `M` for a mirror, and `X`/`Y` for an
+//!   attractor that only pulls along its row/column, with `x`/`y` for the repulsors.
+//! - `=` is a wall and `_` a hole (see `Terrain`).
+//! - A decimal number stands for that many vacuum cells in a row.
+//! - A cell may be followed by `!` if it's conflicted and `+` if it's passable. Marked vacuum
+//!   cells must be written with `.`, not as part of a run.
+//!
+//! For example, `Board::stock_testing_empty()` is `5x5:5/5/2@2/5/5`. A board may have several
+//! automatons, but needs at least one.
+
+use crate::*;
+use displaydoc::Display;
+use std::fmt::Write;
+
+/// Bad board notation: {}
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    /// expected a `WxH:` size prefix
+    MissingSize,
+    /// the size must be two numbers from 1 to 65535, like `11x11`
+    BadSize,
+    /// {0}
+    Shape(ShapeError),
+    /// expected {expected} columns but found {found}
+    ColumnCount { expected: usize, found: usize },
+    /// column {column} should have {expected} cells but has {found}
+    ColumnLength {
+        column: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// unexpected character {ch:?} at offset {at}
+    UnexpectedChar { ch: char, at: usize },
+    /// mark at offset {at} doesn't follow a cell
+    StrayMark { at: usize },
+    /// there is no automaton on the board
+    NoAutomaton,
+}
+
+fn particle_char(p: Particle) -> char {
+    match p {
+        Particle::Repulsor => 'R',
+        Particle::Attractor => 'A',
+        Particle::Automaton => '@',
+        Particle::Vacuum => '.',
+        Particle::Blocker => '#',
+        Particle::Mirror => 'M',
+        Particle::RowAttractor => 'X',
+        Particle::ColumnAttractor => 'Y',
+        Particle::RowRepulsor => 'x',
+        Particle::ColumnRepulsor => 'y',
+    }
+}
+
+fn char_particle(ch: char) -> Option<Particle> {
+    match ch {
+        'R' => Some(Particle::Repulsor),
+        'A' => Some(Particle::Attractor),
+        '@' => Some(Particle::Automaton),
+        '.' => Some(Particle::Vacuum),
+        '#' => Some(Particle::Blocker),
+        'M' => Some(Particle::Mirror),
+        'X' => Some(Particle::RowAttractor),
+        'Y' => Some(Particle::ColumnAttractor),
+        'x' => Some(Particle::RowRepulsor),
+        'y' => Some(Particle::ColumnRepulsor),
+        _ => None,
+    }
+}
+
+impl Board {
+    /// Write the position in board notation (see the notation module docs).
+    pub fn to_notation(&self) -> String {
+        let torus = if self.topology == Topology::Torus {
+            "~"
+        } else {
+            ""
+        };
+        let mut out = match self.tiling {
+            Tiling::Square => format!("{}x{}{}:", self.size.x, self.size.y, torus),
+            Tiling::Hex => format!("{}x{}h{}:", self.size.x, self.size.y, torus),
+            Tiling::Hyper(shape) => format!("{}{}:", shape, torus),
+        };
+        for x in 0..self.size.x {
+            if x != 0 {
+                out.push('/');
+            }
+            let mut run = 0;
+            for y in 0..self.size.y {
+                let cell = self.particles[Coord { x, y }.ix()];
+                let marked = cell.conflict() || cell.passable();
+                let floor = cell.terrain() == Terrain::Floor;
+                if cell.what().is_vacuum() && !marked && floor {
+                    run += 1;
+                    continue;
+                }
+                if run != 0 {
+                    write!(out, "{}", run).unwrap();
+                    run = 0;
+                }
+                out.push(match cell.terrain() {
+                    Terrain::Floor => particle_char(cell.what()),
+                    Terrain::Wall => '=',
+                    Terrain::Hole => '_',
+                });
+                if cell.conflict() {
+                    out.push('!');
+                }
+                if cell.passable() {
+                    out.push('+');
+                }
+            }
+            if run != 0 {
+                write!(out, "{}", run).unwrap();
+            }
+        }
+        out
+    }
+
+    /// Parse a position in board notation (see the notation module docs).
+    ///
+    /// The board must have at least one automaton.
+    pub fn from_notation(s: &str) -> Result<Board, NotationError> {
+        use NotationError::*;
+
+        let colon = s.find(':').ok_or(MissingSize)?;
+        let (size, body) = (&s[..colon], &s[colon + 1..]);
+        let (size, topology) = match size.strip_suffix('~') {
+            Some(size) => (size, Topology::Torus),
+            None => (size, Topology::Flat),
+        };
+        let (size, tiling) = match size.strip_suffix('h') {
+            Some(size) => (size, Tiling::Hex),
+            None => (size, Tiling::Square),
+        };
+        let parse_dim = |d: &str| match d.parse::<u16>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(BadSize),
+        };
+        let extents = size
+            .split('x')
+            .map(parse_dim)
+            .collect::<Result<SmallVec<[u16; 4]>, _>>()?;
+        let (size, tiling) = match (extents.as_slice(), tiling) {
+            (&[x, y], tiling) => (Coord { x, y }, tiling),
+            (&[_, _, _, ..], Tiling::Square) => {
+                let shape = crate::Shape::new(&extents).map_err(Shape)?;
+                (shape.size(), Tiling::Hyper(shape))
+            }
+            _ => return Err(BadSize),
+        };
+        let (w, h) = size.ix();
+
+        let columns = body.split('/').count();
+        if columns != w {
+            return Err(ColumnCount {
+                expected: w,
+                found: columns,
+            });
+        }
+
+        let vacuum = Cell::new(Particle::Vacuum);
+        // The size comes from the input, so don't trust it with an allocation up front.
+        let mut cells = Vec::new();
+        let mut offset = colon + 1;
+
+        for (column, group) in body.split('/').enumerate() {
+            let start = cells.len();
+            let mut run: Option<usize> = None;
+            // Whether the last thing pushed was a single cell that can take marks.
+            let mut markable = false;
+
+            for (i, ch) in group.char_indices() {
+                let at = offset + i;
+                if let Some(d) = ch.to_digit(10) {
+                    // Give up on a run as soon as it's too long for the column, before it gets
+                    // long enough to run out of memory.
+                    let n = run.unwrap_or(0) * 10 + d as usize;
+                    let filled = cells.len() - start;
+                    if n > h.saturating_sub(filled) {
+                        return Err(ColumnLength {
+                            column,
+                            expected: h,
+                            found: filled + n,
+                        });
+                    }
+                    run = Some(n);
+                    markable = false;
+                    continue;
+                }
+                if let Some(n) = run.take() {
+                    cells.extend(std::iter::repeat_n(vacuum, n));
+                }
+                match ch {
+                    '!' | '+' if markable => {
+                        let cell: &mut Cell = cells.last_mut().unwrap();
+                        if ch == '!' {
+                            cell.set_conflict(true);
+                        } else {
+                            cell.set_passable(true);
+                        }
+                    }
+                    '!' | '+' => return Err(StrayMark { at }),
+                    '=' | '_' => {
+                        let terrain = if ch == '=' {
+                            Terrain::Wall
+                        } else {
+                            Terrain::Hole
+                        };
+                        cells.push(vacuum.with_terrain(terrain));
+                        markable = false;
+                    }
+                    _ => {
+                        let what = char_particle(ch).ok_or(UnexpectedChar { ch, at })?;
+                        cells.push(Cell::new(what));
+                        markable = true;
+                    }
+                }
+            }
+            if let Some(n) = run.take() {
+                cells.extend(std::iter::repeat_n(vacuum, n));
+            }
+
+            let found = cells.len() - start;
+            if found != h {
+                return Err(ColumnLength {
+                    column,
+                    expected: h,
+                    found,
+                });
+            }
+            offset += group.len() + 1;
+        }
+
+        let mut board = Board {
+            particles: Grid::from_shape_vec((w, h), cells).expect("columns were counted"),
+            size,
+            automatons: SmallVec::new(),
+            conflict_list: SmallVec::new(),
+            passable_list: SmallVec::new(),
+            topology,
+            tiling,
+            index: LineIndex::default(),
+            hash: 0,
+        };
+        board.rebuild_lists();
+        if board.automatons.is_empty() {
+            return Err(NoAutomaton);
+        }
+        Ok(board)
+    }
+}
+
+impl std::str::FromStr for Board {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Board, NotationError> {
+        Board::from_notation(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn stock_boards_round_trip() {
+        for board in &[
+            Board::stock_two_player(),
+            Board::stock_testing(),
+            Board::stock_testing_empty(),
+        ] {
+            let s = board.to_notation();
+            assert_eq!(&Board::from_notation(&s).unwrap(), board, "{}", s);
+        }
+    }
+
+    #[test]
+    fn stock_notation() {
+        assert_eq!(
+            Board::stock_testing_empty().to_notation(),
+            "5x5:5/5/2@2/5/5"
+        );
+        assert_eq!(
+            Board::stock_two_player().to_notation(),
+            "11x11:RR2RRR2RR/3ARRRA3/11/11/AA7AA/RR3@3RR/AA7AA/11/11/3ARRRA3/RR2RRR2RR"
+        );
+    }
+
+    #[test]
+    fn marks_round_trip() {
+        let board: Board = "3x4:R!.+2/1@.!A+!/4".parse().unwrap();
+        assert_eq!(board.automatons.as_slice(), &[Coord { x: 1, y: 1 }]);
+        assert_eq!(board.conflict_list.len(), 3);
+        assert_eq!(board.passable_list.len(), 2);
+        assert!(board.is_conflict(Coord { x: 1, y: 2 }));
+        assert_eq!(board.to_notation(), "3x4:R!.+2/1@.!A!+/4");
+    }
+
+    #[test]
+    fn parse_errors() {
+        use NotationError::*;
+        let err = |s: &str| Board::from_notation(s).unwrap_err();
+        assert_eq!(err("5/5/2@2/5/5"), MissingSize);
+        assert_eq!(err("5x:5/5/2@2/5/5"), BadSize);
+        assert_eq!(err("0x5:"), BadSize);
+        assert_eq!(err("3x3x3h:"), BadSize);
+        assert_eq!(err("3x1x3:"), Shape(ShapeError::TooShort(1)));
+        assert_eq!(
+            err("5x5:5/2@2/5/5"),
+            ColumnCount {
+                expected: 5,
+                found: 4
+            }
+        );
+        assert_eq!(
+            err("5x5:5/5/2@3/5/5"),
+            ColumnLength {
+                column: 2,
+                expected: 5,
+                found: 6
+            }
+        );
+        // Overlong runs are caught at the first digit too many, however many digits follow.
+        assert_eq!(
+            err("1x1:99999999999999"),
+            ColumnLength {
+                column: 0,
+                expected: 1,
+                found: 9
+            }
+        );
+        assert_eq!(
+            err("1x2:@99999999999999999999999"),
+            ColumnLength {
+                column: 0,
+                expected: 2,
+                found: 10
+            }
+        );
+        assert_eq!(
+            err("2x65535:@65534/65536"),
+            ColumnLength {
+                column: 1,
+                expected: 65535,
+                found: 65536
+            }
+        );
+        assert_eq!(err("5x5:5/5/2@Q1/5/5"), UnexpectedChar { ch: 'Q', at: 10 });
+        assert_eq!(err("5x5:5/5!/2@2/5/5"), StrayMark { at: 7 });
+        assert_eq!(err("5x5:5/5/5/5/5"), NoAutomaton);
+    }
+
+    #[test]
+    fn several_automatons() {
+        let board: Board = "5x5:5/1@3/5/5/4@".parse().unwrap();
+        assert_eq!(
+            board.automatons.as_slice(),
+            &[Coord { x: 1, y: 1 }, Coord { x: 4, y: 4 }]
+        );
+        assert_eq!(board.to_notation(), "5x5:5/1@3/5/5/4@");
+    }
+}
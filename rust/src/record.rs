@@ -0,0 +1,856 @@
+//! Game records: everything needed to replay a game, and check it comes out the same way.
+//!
+//! A record is built by feeding it a game's events (`GameRecord::observe`), and has a text form
+//! in the spirit of chess's PGN. Tags give the starting conditions, then each round gets a line
+//! with its actions, in order, and its outcome:
+//!
+//! ```text
+//! [Board "5x5:5/5/2@2/5/5"]
+//! [Players "0 1"]
+//! [Seat "1 ada"]
+//! [ColumnRule "true"]
+//! [Goals "1@2,1"]
+//!
+//! 1. 0:0,0-0,1 1:1,1-0,1 | 0:0,0-0,3 1:4,4-4,3 = 0:ok 1:nosource @2,1
+//! ```
+//!
+//! `Players` lists the Pids in the roster, in order, and each taken seat gets a `Seat` tag with
+//! its Pid and the name sitting there. The other rule choices get tags of their own (`RepulsorFirst`, `MergeMoves`, `GoalRounds`,
+//! `AttractorMoves` and `RepulsorMoves`), which are left out when they're at their defaults.
+//!
+//! - `P:x,y-x,y` is an accepted move by player P. A later move by the same player in the same
+//!   round replaces it.
+//! - `P~` is player P withdrawing their pending move.
+//! - `|` is an attempt to complete the round that ended in conflicts.
+//! - `=` starts the outcome: one result per locked move, named by the player who proposed it,
+//!   then the location of each automaton after its step, in order of coordinate (by x, then y).
+//!   Results are `ok`, `nosource`, `cycle`, `disallowed` or `blocked@x,y`.
+//!
+//! A round without an outcome is still in progress.
+
+use crate::*;
+use displaydoc::Display;
+use std::fmt::Write;
+
+/// Something that happened during a round, before it was complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Action {
+    /// A move was proposed and accepted.
+    Propose(Move),
+    /// A player withdrew their pending move.
+    Withdraw(Pid),
+    /// Completing the round was attempted, but ended in conflicts.
+    Conflict,
+}
+
+/// How a round ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoundOutcome {
+    pub results: Vec<(Move, MoveResult)>,
+    /// Where the automatons were once they stepped.
+    pub automatons: Vec<Coord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoundRecord {
+    pub actions: Vec<Action>,
+    /// None while the round is in progress.
+    pub outcome: Option<RoundOutcome>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameRecord {
+    /// The starting position, in board notation.
+    pub board: String,
+    pub goals: Vec<(Coord, Pid)>,
+    /// The players as they were when recording started.
+    pub roster: Roster,
+    pub rules: RuleSet,
+    pub rounds: Vec<RoundRecord>,
+    /// Results of the round being completed, waiting for its TurnOver.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    results: Vec<(Move, MoveResult)>,
+    /// Rounds taken back, in case they're put back.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    undone: Vec<RoundRecord>,
+}
+
+/// Replay went wrong: {}
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// the starting board is bad: {0}
+    Board(NotationError),
+    /// {0}
+    Inconsistent(BoardErrors),
+    /// in round {round}, player {who} had a move rejected: {feedback}
+    Rejected {
+        round: usize,
+        who: Pid,
+        feedback: MoveFeedback,
+    },
+    /// in round {round}, player {who} had no move to withdraw
+    NothingToWithdraw { round: usize, who: Pid },
+    /// in round {round}, not every player had a move in when the round was resolved
+    NotReady { round: usize },
+    /// in round {round}, the recorded conflict didn't happen
+    NoConflict { round: usize },
+    /// in round {round}, the moves conflicted but the record says the round was completed
+    UnexpectedConflict { round: usize },
+    /// in round {round}, the move results differ from the record
+    ResultsDiffer { round: usize },
+    /// in round {round}, an automaton ended up at {found} instead of {expected}
+    AutomatonDiffers {
+        round: usize,
+        expected: Coord,
+        found: Coord,
+    },
+    /// in round {round}, the record has the wrong number of automatons
+    AutomatonCount { round: usize },
+}
+
+/// Bad game record, line {line}: {kind}
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub struct ParseRecordError {
+    pub line: usize,
+    pub kind: ParseRecordErrorKind,
+}
+
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum ParseRecordErrorKind {
+    /// unknown tag {0:?}
+    UnknownTag(String),
+    /// malformed tag
+    BadTagSyntax,
+    /// bad value for the {0} tag
+    BadTag(&'static str),
+    /// missing the {0} tag
+    MissingTag(&'static str),
+    /// bad token {0:?}
+    BadToken(String),
+    /// expected round {0}
+    RoundNumber(usize),
+    /// there's a result for player {0}, who has no move this round
+    NoMoveFor(Pid),
+    /// the round's outcome is missing the automaton locations
+    MissingAutomaton,
+}
+
+impl GameRecord {
+    /// Start recording a game from its current position.
+    ///
+    /// The game should be between rounds, with no moves pending.
+    pub fn new(game: &Game) -> GameRecord {
+        debug_assert!(game.pending_moves.is_empty());
+        GameRecord {
+            board: game.board.to_notation(),
+            goals: game.goals.to_vec(),
+            roster: game.roster.clone(),
+            rules: game.rules,
+            rounds: Vec::new(),
+            results: Vec::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    /// The round in progress, starting one if need be.
+    fn current(&mut self) -> &mut RoundRecord {
+        if self.rounds.last().is_none_or(|r| r.outcome.is_some()) {
+            self.rounds.push(RoundRecord::default());
+        }
+        self.rounds.last_mut().unwrap()
+    }
+
+    /// Forget the round in progress, if any.
+    fn drop_unfinished(&mut self) {
+        if self.rounds.last().is_some_and(|r| r.outcome.is_none()) {
+            self.rounds.pop();
+        }
+    }
+
+    /// Record an event from the game being recorded.
+    ///
+    /// Events must be observed in order, and all of them. Calling Game::update_automaton outside
+    /// of a round isn't recorded, and will make the replay diverge.
+    pub fn observe(&mut self, event: &GameEvent) {
+        use GameEvent::*;
+        match event {
+            // Claims only happen before the first round, so they can go straight into the goals.
+            CornerClaimed { who, at } => self.goals.push((*at, *who)),
+            MoveAck { m, .. } => self.current().actions.push(Action::Propose(*m)),
+            MoveWithdrawn { who } => self.current().actions.push(Action::Withdraw(*who)),
+            Conflict { .. } => {
+                // One attempt can conflict on several squares.
+                let round = self.current();
+                if round.actions.last() != Some(&Action::Conflict) {
+                    round.actions.push(Action::Conflict);
+                }
+            }
+            DoMove { m, result } => self.results.push((*m, result.clone())),
+            TurnOver { automatons_to, .. } => {
+                let results = std::mem::take(&mut self.results);
+                self.current().outcome = Some(RoundOutcome {
+                    results,
+                    automatons: automatons_to.to_vec(),
+                });
+                self.undone.clear();
+            }
+            // The game discards the round in progress either way, so the record does too.
+            RoundUndone => {
+                self.drop_unfinished();
+                if let Some(round) = self.rounds.pop() {
+                    self.undone.push(round);
+                }
+            }
+            RoundRedone => {
+                self.drop_unfinished();
+                if let Some(round) = self.undone.pop() {
+                    self.rounds.push(round);
+                }
+            }
+            MoveInvalid { .. } | AutomatonMove { .. } => {}
+        }
+    }
+
+    /// Replay the record, checking every round comes out as recorded, and return the game. Like a
+    /// new game, it doesn't queue events until `emit_events` is set.
+    pub fn replay(&self) -> Result<Game, ReplayError> {
+        use ReplayError::*;
+
+        let board = crate::Board::from_notation(&self.board).map_err(Board)?;
+        let player_count = self.roster.len() as u8;
+        let mut game = Game::new(board, player_count, self.rules).map_err(Inconsistent)?;
+        game.roster = self.roster.clone();
+        game.goals = SmallVec::from_slice(&self.goals);
+
+        let ready = |game: &Game| game.pending_moves.len() == game.player_count() as usize;
+
+        for (i, record) in self.rounds.iter().enumerate() {
+            let round = i + 1;
+            for action in &record.actions {
+                match *action {
+                    Action::Propose(m) => match game.propose_move(m).0 {
+                        MoveFeedback::Committed | MoveFeedback::Replaced => {}
+                        feedback => {
+                            return Err(Rejected {
+                                round,
+                                who: m.who,
+                                feedback,
+                            })
+                        }
+                    },
+                    Action::Withdraw(who) => {
+                        game.withdraw_move(who)
+                            .ok_or(NothingToWithdraw { round, who })?;
+                    }
+                    Action::Conflict => {
+                        if !ready(&game) {
+                            return Err(NotReady { round });
+                        }
+                        if game.try_complete_round().is_ok() {
+                            return Err(NoConflict { round });
+                        }
+                    }
+                }
+            }
+
+            if let Some(outcome) = &record.outcome {
+                if !ready(&game) {
+                    return Err(NotReady { round });
+                }
+                let results = game
+                    .try_complete_round()
+                    .map_err(|()| UnexpectedConflict { round })?;
+                if results.as_slice() != outcome.results.as_slice() {
+                    return Err(ResultsDiffer { round });
+                }
+                if game.board.automatons.len() != outcome.automatons.len() {
+                    return Err(AutomatonCount { round });
+                }
+                for (&found, &expected) in game.board.automatons.iter().zip(&outcome.automatons) {
+                    if found != expected {
+                        return Err(AutomatonDiffers {
+                            round,
+                            expected,
+                            found,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(game)
+    }
+
+    /// Parse a record from its text form (see the record module docs).
+    pub fn parse(s: &str) -> Result<GameRecord, ParseRecordError> {
+        use ParseRecordErrorKind::*;
+
+        let mut board = None;
+        let mut roster: Option<Roster> = None;
+        let mut seats = Vec::new();
+        let mut use_column_rule = None;
+        let mut rules = RuleSet::default();
+        let mut goals = None;
+        let mut rounds = Vec::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let err = |kind| ParseRecordError { line: i + 1, kind };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                let (name, value) = parse_tag(line).ok_or_else(|| err(BadTagSyntax))?;
+                match name {
+                    "Board" => board = Some(value.to_string()),
+                    "Players" => {
+                        let mut players = Roster::new(0);
+                        for pid in value.split_whitespace() {
+                            let pid = parse_pid(pid).ok_or_else(|| err(BadTag("Players")))?;
+                            players.register(pid).map_err(|_| err(BadTag("Players")))?;
+                        }
+                        roster = Some(players);
+                    }
+                    "Seat" => {
+                        let space = value.find(' ').ok_or_else(|| err(BadTag("Seat")))?;
+                        let pid = parse_pid(&value[..space]).ok_or_else(|| err(BadTag("Seat")))?;
+                        seats.push((i + 1, pid, value[space + 1..].to_string()));
+                    }
+                    "ColumnRule" => {
+                        use_column_rule =
+                            Some(value.parse().map_err(|_| err(BadTag("ColumnRule")))?)
+                    }
+                    "RepulsorFirst" => {
+                        rules.repulsor_first =
+                            value.parse().map_err(|_| err(BadTag("RepulsorFirst")))?
+                    }
+                    "MergeMoves" => {
+                        rules.merge_identical_moves =
+                            value.parse().map_err(|_| err(BadTag("MergeMoves")))?
+                    }
+                    "GoalRounds" => {
+                        let n = value.parse().map_err(|_| err(BadTag("GoalRounds")))?;
+                        rules.win = WinCondition::GoalRounds(n);
+                    }
+                    "AttractorMoves" => {
+                        rules.movement.attractor =
+                            value.parse().map_err(|_| err(BadTag("AttractorMoves")))?
+                    }
+                    "RepulsorMoves" => {
+                        rules.movement.repulsor =
+                            value.parse().map_err(|_| err(BadTag("RepulsorMoves")))?
+                    }
+                    "Goals" => {
+                        let parsed: Option<Vec<_>> =
+                            value.split_whitespace().map(parse_goal).collect();
+                        goals = Some(parsed.ok_or_else(|| err(BadTag("Goals")))?);
+                    }
+                    _ => return Err(err(UnknownTag(name.to_string()))),
+                }
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let expected = rounds.len() + 1;
+            if tokens.next() != Some(&format!("{}.", expected)[..]) {
+                return Err(err(RoundNumber(expected)));
+            }
+
+            let mut record = RoundRecord::default();
+            while let Some(token) = tokens.next() {
+                if token == "=" {
+                    let mut results = Vec::new();
+                    let mut automatons = Vec::new();
+                    for token in tokens.by_ref() {
+                        let bad = || err(BadToken(token.to_string()));
+                        if let Some(c) = token.strip_prefix('@') {
+                            automatons.push(parse_coord(c).ok_or_else(bad)?);
+                            continue;
+                        }
+                        if !automatons.is_empty() {
+                            return Err(bad());
+                        }
+                        let (who, result) = parse_result(token).ok_or_else(bad)?;
+                        let m = record
+                            .actions
+                            .iter()
+                            .rev()
+                            .find_map(|a| match a {
+                                Action::Propose(m) if m.who == who => Some(*m),
+                                _ => None,
+                            })
+                            .ok_or_else(|| err(NoMoveFor(who)))?;
+                        results.push((m, result));
+                    }
+                    if automatons.is_empty() {
+                        return Err(err(MissingAutomaton));
+                    }
+                    record.outcome = Some(RoundOutcome {
+                        results,
+                        automatons,
+                    });
+                    break;
+                }
+                let action = parse_action(token).ok_or_else(|| err(BadToken(token.into())))?;
+                record.actions.push(action);
+            }
+            if let Some(token) = tokens.next() {
+                return Err(err(BadToken(token.to_string())));
+            }
+            rounds.push(record);
+        }
+
+        let missing = |tag| ParseRecordError {
+            line: 0,
+            kind: MissingTag(tag),
+        };
+        let mut roster = roster.ok_or_else(|| missing("Players"))?;
+        for (line, pid, name) in seats {
+            roster.seat_at(pid, &name).map_err(|_| ParseRecordError {
+                line,
+                kind: BadTag("Seat"),
+            })?;
+        }
+        Ok(GameRecord {
+            board: board.ok_or_else(|| missing("Board"))?,
+            goals: goals.unwrap_or_default(),
+            roster,
+            rules: RuleSet {
+                tie: match use_column_rule.ok_or_else(|| missing("ColumnRule"))? {
+                    true => TieRule::ColumnRule,
+                    false => TieRule::Freeze,
+                },
+                ..rules
+            },
+            rounds,
+            results: Vec::new(),
+            undone: Vec::new(),
+        })
+    }
+}
+
+fn parse_tag(line: &str) -> Option<(&str, &str)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let space = inner.find(' ')?;
+    let value = inner[space + 1..].strip_prefix('"')?.strip_suffix('"')?;
+    Some((&inner[..space], value))
+}
+
+fn parse_coord(s: &str) -> Option<Coord> {
+    let comma = s.find(',')?;
+    Some(Coord {
+        x: s[..comma].parse().ok()?,
+        y: s[comma + 1..].parse().ok()?,
+    })
+}
+
+fn parse_pid(s: &str) -> Option<Pid> {
+    s.parse().ok().map(Pid)
+}
+
+fn parse_goal(s: &str) -> Option<(Coord, Pid)> {
+    let at = s.find('@')?;
+    Some((parse_coord(&s[at + 1..])?, parse_pid(&s[..at])?))
+}
+
+fn parse_action(s: &str) -> Option<Action> {
+    if s == "|" {
+        return Some(Action::Conflict);
+    }
+    if let Some(who) = s.strip_suffix('~') {
+        return Some(Action::Withdraw(parse_pid(who)?));
+    }
+    let colon = s.find(':')?;
+    let dash = s.find('-')?;
+    Some(Action::Propose(Move {
+        who: parse_pid(&s[..colon])?,
+        from: parse_coord(&s[colon + 1..dash])?,
+        to: parse_coord(&s[dash + 1..])?,
+    }))
+}
+
+fn parse_result(s: &str) -> Option<(Pid, MoveResult)> {
+    let colon = s.find(':')?;
+    let result = match &s[colon + 1..] {
+        "ok" => MoveResult::Applied,
+        "nosource" => MoveResult::NoSource,
+        "cycle" => MoveResult::Cycle,
+        "disallowed" => MoveResult::Disallowed,
+        r => MoveResult::OccupiedAt(parse_coord(r.strip_prefix("blocked@")?)?),
+    };
+    Some((parse_pid(&s[..colon])?, result))
+}
+
+/// Coordinates are written `x,y` in records, to keep tokens free of spaces.
+struct Short(Coord);
+
+impl core::fmt::Display for Short {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{},{}", self.0.x, self.0.y)
+    }
+}
+
+impl core::fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        writeln!(f, "[Board \"{}\"]", self.board)?;
+        let mut players = String::new();
+        for player in self.roster.iter() {
+            if !players.is_empty() {
+                players.push(' ');
+            }
+            write!(players, "{}", player.pid.0)?;
+        }
+        writeln!(f, "[Players \"{}\"]", players)?;
+        for player in self.roster.iter() {
+            if let Some(name) = &player.name {
+                writeln!(f, "[Seat \"{} {}\"]", player.pid.0, name)?;
+            }
+        }
+        let column_rule = self.rules.tie == TieRule::ColumnRule;
+        writeln!(f, "[ColumnRule \"{}\"]", column_rule)?;
+        let defaults = RuleSet::default();
+        if self.rules.repulsor_first != defaults.repulsor_first {
+            writeln!(f, "[RepulsorFirst \"{}\"]", self.rules.repulsor_first)?;
+        }
+        if self.rules.merge_identical_moves != defaults.merge_identical_moves {
+            writeln!(f, "[MergeMoves \"{}\"]", self.rules.merge_identical_moves)?;
+        }
+        if let WinCondition::GoalRounds(n) = self.rules.win {
+            writeln!(f, "[GoalRounds \"{}\"]", n)?;
+        }
+        let movement = self.rules.movement;
+        if movement.attractor != Movement::Rook {
+            writeln!(f, "[AttractorMoves \"{}\"]", movement.attractor)?;
+        }
+        if movement.repulsor != Movement::Rook {
+            writeln!(f, "[RepulsorMoves \"{}\"]", movement.repulsor)?;
+        }
+        let mut goals = String::new();
+        for (c, who) in &self.goals {
+            if !goals.is_empty() {
+                goals.push(' ');
+            }
+            write!(goals, "{}@{}", who.0, Short(*c))?;
+        }
+        writeln!(f, "[Goals \"{}\"]", goals)?;
+
+        for (i, round) in self.rounds.iter().enumerate() {
+            writeln!(f)?;
+            write!(f, "{}.", i + 1)?;
+            for action in &round.actions {
+                match action {
+                    Action::Propose(m) => {
+                        write!(f, " {}:{}-{}", m.who.0, Short(m.from), Short(m.to))?
+                    }
+                    Action::Withdraw(who) => write!(f, " {}~", who.0)?,
+                    Action::Conflict => write!(f, " |")?,
+                }
+            }
+            if let Some(outcome) = &round.outcome {
+                write!(f, " =")?;
+                for (m, result) in &outcome.results {
+                    write!(f, " {}:", m.who.0)?;
+                    match result {
+                        MoveResult::Applied => write!(f, "ok")?,
+                        MoveResult::NoSource => write!(f, "nosource")?,
+                        MoveResult::Cycle => write!(f, "cycle")?,
+                        MoveResult::Disallowed => write!(f, "disallowed")?,
+                        MoveResult::OccupiedAt(c) => write!(f, "blocked@{}", Short(*c))?,
+                    }
+                }
+                for &c in &outcome.automatons {
+                    write!(f, " @{}", Short(c))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for GameRecord {
+    type Err = ParseRecordError;
+
+    fn from_str(s: &str) -> Result<GameRecord, ParseRecordError> {
+        GameRecord::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::{c, mv};
+    use crate::*;
+
+    /// Play a few rounds on the stock board, recording as we go.
+    fn recorded_game() -> (Game, GameRecord) {
+        let mut game = Game::new(Board::stock_two_player(), 2, RuleSet::default()).unwrap();
+        game.emit_events = true;
+        game.goals.push((c(0, 0), Pid(0)));
+        game.goals.push((c(10, 10), Pid(1)));
+        let mut record = GameRecord::new(&game);
+
+        let mut play = |game: &mut Game, moves: &[Move], withdraw: Option<Pid>| {
+            for &m in moves {
+                game.propose_move(m);
+            }
+            if let Some(who) = withdraw {
+                game.withdraw_move(who);
+            }
+            if game.pending_moves.len() == 2 {
+                let _ = game.try_complete_round();
+            }
+            for event in game.drain_events() {
+                record.observe(&event);
+            }
+        };
+
+        play(
+            &mut game,
+            &[mv(0, c(4, 1), c(4, 3)), mv(1, c(6, 1), c(6, 3))],
+            None,
+        );
+        play(
+            &mut game,
+            &[mv(0, c(0, 4), c(2, 4)), mv(1, c(1, 4), c(2, 4))],
+            None,
+        );
+        play(
+            &mut game,
+            &[mv(0, c(0, 4), c(0, 3)), mv(1, c(1, 4), c(3, 4))],
+            None,
+        );
+        play(&mut game, &[mv(0, c(9, 4), c(9, 3))], Some(Pid(0)));
+        play(
+            &mut game,
+            &[mv(0, c(9, 4), c(8, 4)), mv(1, c(8, 4), c(8, 3))],
+            None,
+        );
+        play(&mut game, &[mv(0, c(10, 5), c(10, 2))], None);
+
+        (game, record)
+    }
+
+    #[test]
+    fn replay_matches() {
+        let (mut game, record) = recorded_game();
+        assert_eq!(record.rounds.len(), 4);
+        assert!(record.rounds[1].actions.contains(&Action::Conflict));
+        assert!(record.rounds[2].actions.contains(&Action::Withdraw(Pid(0))));
+        assert!(record.rounds[3].outcome.is_none());
+        // The replay is quiet unless asked otherwise.
+        game.emit_events = false;
+        assert_eq!(record.replay().unwrap(), game);
+    }
+
+    #[test]
+    fn undone_rounds_are_dropped() {
+        let (mut game, mut record) = recorded_game();
+        let observe = |game: &mut Game, record: &mut GameRecord| {
+            for event in game.drain_events() {
+                record.observe(&event);
+            }
+        };
+        assert!(game.undo_round());
+        assert!(game.undo_round());
+        observe(&mut game, &mut record);
+        assert_eq!(record.rounds.len(), 1);
+        assert!(game.redo_round());
+        observe(&mut game, &mut record);
+        assert_eq!(record.rounds.len(), 2);
+
+        let replayed = record.replay().unwrap();
+        assert!(!replayed.emit_events && replayed.events.is_empty());
+        assert_eq!(replayed.board, game.board);
+        assert_eq!(replayed.history(), game.history());
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let (_, record) = recorded_game();
+        let text = record.to_string();
+        assert_eq!(GameRecord::parse(&text).unwrap(), record, "{}", text);
+    }
+
+    #[test]
+    fn registered_players() {
+        let mut game = Game::new(Board::stock_two_player(), 1, RuleSet::default()).unwrap();
+        game.roster.register(Pid(7)).unwrap();
+        game.roster.seat_at(Pid(7), "ada").unwrap();
+        game.emit_events = true;
+        let mut record = GameRecord::new(&game);
+        game.propose_move(mv(0, c(4, 1), c(4, 3)));
+        game.propose_move(mv(7, c(6, 1), c(6, 3)));
+        game.try_complete_round().unwrap();
+        for event in game.drain_events() {
+            record.observe(&event);
+        }
+
+        let text = record.to_string();
+        assert!(
+            text.contains("[Players \"0 7\"]\n[Seat \"7 ada\"]\n"),
+            "{}",
+            text
+        );
+        assert_eq!(GameRecord::parse(&text).unwrap(), record, "{}", text);
+        game.emit_events = false;
+        assert_eq!(record.replay().unwrap(), game);
+    }
+
+    #[test]
+    fn rule_tags() {
+        let (_, mut record) = recorded_game();
+        record.rules = RuleSet {
+            tie: TieRule::Freeze,
+            repulsor_first: false,
+            merge_identical_moves: false,
+            win: WinCondition::GoalRounds(3),
+            movement: PieceMovement {
+                attractor: Movement::Rook,
+                repulsor: Movement::Knight,
+            },
+        };
+        let text = record.to_string();
+        assert!(text.contains("[MergeMoves \"false\"]"), "{}", text);
+        assert!(text.contains("[RepulsorMoves \"knight\"]"), "{}", text);
+        assert!(!text.contains("AttractorMoves"), "{}", text);
+        assert_eq!(GameRecord::parse(&text).unwrap(), record, "{}", text);
+    }
+
+    #[test]
+    fn text_format() {
+        let text = "[Board \"5x5:A4/5/2@2/5/5\"]\n\
+                    [Players \"0 1\"]\n\
+                    [ColumnRule \"true\"]\n\
+                    [Goals \"1@2,1\"]\n\
+                    \n\
+                    1. 0:0,0-0,1 1:1,1-0,1 | 0:0,0-0,3 1:4,4-4,3 = 0:ok 1:nosource @2,2";
+        let record: GameRecord = text.parse().unwrap();
+        assert_eq!(record.to_string(), text);
+        assert_eq!(
+            record.rounds[0].actions,
+            vec![
+                Action::Propose(mv(0, c(0, 0), c(0, 1))),
+                Action::Propose(mv(1, c(1, 1), c(0, 1))),
+                Action::Conflict,
+                Action::Propose(mv(0, c(0, 0), c(0, 3))),
+                Action::Propose(mv(1, c(4, 4), c(4, 3))),
+            ]
+        );
+        let outcome = record.rounds[0].outcome.as_ref().unwrap();
+        assert_eq!(
+            outcome.results[1],
+            (mv(1, c(4, 4), c(4, 3)), MoveResult::NoSource)
+        );
+        record.replay().unwrap();
+    }
+
+    #[test]
+    fn several_automatons() {
+        let text = "[Board \"5x5:5/1@1A1/2@2/2R2/5\"]\n\
+                    [Players \"0 1\"]\n\
+                    [ColumnRule \"true\"]\n\
+                    [Goals \"\"]\n\
+                    \n\
+                    1. 0:0,0-0,1 1:4,4-4,3 = 0:nosource 1:nosource @1,2 @2,2";
+        let mut record: GameRecord = text.parse().unwrap();
+        assert_eq!(record.to_string(), text);
+        record.replay().unwrap();
+
+        record.rounds[0].outcome.as_mut().unwrap().automatons.pop();
+        assert_eq!(
+            record.replay(),
+            Err(ReplayError::AutomatonCount { round: 1 })
+        );
+    }
+
+    #[test]
+    fn replay_catches_tampering() {
+        let (_, record) = recorded_game();
+
+        let mut bad = record.clone();
+        bad.rounds[0].outcome.as_mut().unwrap().automatons[0] = c(0, 0);
+        assert!(matches!(
+            bad.replay(),
+            Err(ReplayError::AutomatonDiffers { round: 1, .. })
+        ));
+
+        let mut bad = record.clone();
+        bad.rounds[0].outcome.as_mut().unwrap().results[0].1 = MoveResult::Cycle;
+        assert_eq!(bad.replay(), Err(ReplayError::ResultsDiffer { round: 1 }));
+
+        let mut bad = record.clone();
+        // Without the re-submissions, the round ends in conflict again.
+        bad.rounds[1].actions.truncate(2);
+        assert_eq!(
+            bad.replay(),
+            Err(ReplayError::UnexpectedConflict { round: 2 })
+        );
+
+        let mut bad = record;
+        bad.board = "5x5:5/5/5/5/5".into();
+        assert_eq!(
+            bad.replay(),
+            Err(ReplayError::Board(NotationError::NoAutomaton))
+        );
+    }
+
+    #[test]
+    fn huge_board_is_rejected() {
+        // A run this long once tried to allocate the whole column before checking it.
+        let text = "[Board \"1x2:@99999999999999999999999\"]\n[Players \"0 1\"]\n\
+                    [ColumnRule \"true\"]\n";
+        let record = GameRecord::parse(text).unwrap();
+        assert_eq!(
+            record.replay(),
+            Err(ReplayError::Board(NotationError::ColumnLength {
+                column: 0,
+                expected: 2,
+                found: 10
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        use ParseRecordErrorKind::*;
+        let err = |s: &str| GameRecord::parse(s).unwrap_err();
+        let tags = "[Board \"5x5:5/5/2@2/5/5\"]\n[Players \"0 1\"]\n[ColumnRule \"true\"]\n";
+        assert_eq!(
+            err("[Board \"5x5:5/5/2@2/5/5\"]").kind,
+            MissingTag("Players")
+        );
+        assert_eq!(err("[Rules \"none\"]").kind, UnknownTag("Rules".into()));
+        assert_eq!(err("[Players 2]").kind, BadTagSyntax);
+        assert_eq!(err("[Players \"0 0\"]").kind, BadTag("Players"));
+        assert_eq!(
+            err(&format!("{}[Seat \"5 bob\"]", tags)),
+            ParseRecordError {
+                line: 4,
+                kind: BadTag("Seat")
+            }
+        );
+        assert_eq!(
+            err(&format!("{}2. 0:0,0-0,1", tags)),
+            ParseRecordError {
+                line: 4,
+                kind: RoundNumber(1)
+            }
+        );
+        assert_eq!(
+            err(&format!("{}1. 0:0,0-0,1 = 1:ok @2,2", tags)).kind,
+            NoMoveFor(Pid(1))
+        );
+        assert_eq!(
+            err(&format!("{}1. 0:0,0-0,1 = 0:ok", tags)).kind,
+            MissingAutomaton
+        );
+        assert_eq!(
+            err(&format!("{}1. 0:0,0/0,1", tags)).kind,
+            BadToken("0:0,0/0,1".into())
+        );
+    }
+}
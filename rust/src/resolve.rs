@@ -0,0 +1,154 @@
+//! Resolution of the locked moves of a round.
+//!
+//! Once conflict resolution is over, no two moves share a source and no two moves share a
+//! destination. Viewing every move as an edge between two squares, each square then has at most
+//! one edge in and at most one edge out, so the move graph falls apart into disjoint chains and
+//! cycles. That is what makes the README's erratum ("sort the moves topologically, with each move
+//! being an edge") cheap to implement.
+
+use crate::*;
+use smallvec::{smallvec, SmallVec};
+
+/// Indices into the move list, in graph order.
+pub(crate) type Path = SmallVec<[usize; 2]>;
+
+/// The locked moves of a round, viewed as a graph on board squares.
+pub(crate) struct MoveGraph<'a> {
+    moves: &'a [Move],
+}
+
+impl<'a> MoveGraph<'a> {
+    pub(crate) fn new(moves: &'a [Move]) -> MoveGraph<'a> {
+        debug_assert!(moves.iter().enumerate().all(|(i, m)| moves[i + 1..]
+            .iter()
+            .all(|o| o.from != m.from && o.to != m.to)));
+        MoveGraph { moves }
+    }
+
+    /// The move leaving a square, if any.
+    fn leaving(&self, c: Coord) -> Option<usize> {
+        self.moves.iter().position(|m| m.from == c)
+    }
+
+    /// The move entering a square, if any.
+    fn entering(&self, c: Coord) -> Option<usize> {
+        self.moves.iter().position(|m| m.to == c)
+    }
+
+    /// Split the graph into chains, each in topological order, and cycles.
+    pub(crate) fn components(&self) -> (SmallVec<[Path; 2]>, SmallVec<[Path; 2]>) {
+        let mut visited: SmallVec<[bool; 2]> = smallvec![false; self.moves.len()];
+        let mut chains = SmallVec::new();
+        let mut cycles = SmallVec::new();
+
+        // Chains start at a move whose source nothing moves into.
+        for head in 0..self.moves.len() {
+            if self.entering(self.moves[head].from).is_some() {
+                continue;
+            }
+            let mut chain = Path::new();
+            let mut next = Some(head);
+            while let Some(i) = next {
+                visited[i] = true;
+                chain.push(i);
+                next = self.leaving(self.moves[i].to);
+            }
+            chains.push(chain);
+        }
+
+        // Everything left over must be on a cycle.
+        for start in 0..self.moves.len() {
+            if visited[start] {
+                continue;
+            }
+            let mut cycle = Path::new();
+            let mut i = start;
+            loop {
+                visited[i] = true;
+                cycle.push(i);
+                i = self
+                    .leaving(self.moves[i].to)
+                    .expect("a move not on a chain is on a cycle");
+                if i == start {
+                    break;
+                }
+            }
+            cycles.push(cycle);
+        }
+
+        (chains, cycles)
+    }
+
+    /// Apply every move to the board, returning the results in the same order as the moves.
+    ///
+    /// The sources of all moves must already be marked passable. Along a chain, a piece that lands
+    /// on the source of the next move carries on with that move too, unless the square had a piece
+    /// of its own; that piece leaves first, and the arriving piece stops there. Pieces on a cycle
+    /// stay where they are. Each piece moves by its own rule, whichever move it's carrying on with.
+    pub(crate) fn apply(
+        &self,
+        board: &mut Board,
+        movement: &PieceMovement,
+    ) -> SmallVec<[(Move, MoveResult); 2]> {
+        let mut results: SmallVec<[Option<MoveResult>; 2]> = smallvec![None; self.moves.len()];
+        let (chains, cycles) = self.components();
+
+        for cycle in &cycles {
+            for &i in cycle {
+                board.settle(self.moves[i].from);
+                results[i] = Some(MoveResult::Cycle);
+            }
+        }
+
+        for chain in &chains {
+            // Walk the runs of each chain back to front, so the piece ahead has always left (or
+            // failed to leave) before the piece behind arrives.
+            let mut end = chain.len();
+            for start in (0..chain.len()).rev() {
+                let from = self.moves[chain[start]].from;
+                if start != 0 && board.is_vacuum(from) {
+                    continue;
+                }
+                self.apply_run(board, movement, &chain[start..end], &mut results);
+                end = start;
+            }
+        }
+
+        self.moves
+            .iter()
+            .zip(results)
+            .map(|(&m, r)| (m, r.expect("every move is on a chain or a cycle")))
+            .collect()
+    }
+
+    /// Carry a single piece along consecutive moves of a chain.
+    fn apply_run(
+        &self,
+        board: &mut Board,
+        movement: &PieceMovement,
+        run: &[usize],
+        results: &mut [Option<MoveResult>],
+    ) {
+        let mut stopped = board.is_vacuum(self.moves[run[0]].from);
+        for &i in run {
+            let m = self.moves[i];
+            results[i] = Some(if stopped {
+                MoveResult::NoSource
+            } else {
+                let rule = movement.of(board.particles[m.from.ix()].what());
+                match board.do_move(m.from, m.to, &rule) {
+                    MoveResult::Applied => MoveResult::Applied,
+                    failed => {
+                        trace!("piece stopped at {coord}", coord = m.from);
+                        board.settle(m.from);
+                        stopped = true;
+                        failed
+                    }
+                }
+            });
+        }
+        if !stopped {
+            board.settle(self.moves[run[run.len() - 1]].to);
+        }
+    }
+}
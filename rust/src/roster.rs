@@ -0,0 +1,216 @@
+//! The players of a game: which Pids are registered, and who is sitting in each seat.
+//!
+//! Every game has one seat per player, identified by its Pid, and its player count is the number of
+//! seats. A new game has Pids 0 up to the player count, and more can be registered, with any Pid.
+//! A seat can be taken by name (say, when someone connects to a server) and given up again; the
+//! game itself only cares that moves come from a registered Pid.
+
+use crate::*;
+use displaydoc::Display;
+
+/// Roster error: {}
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RosterError {
+    /// player {0} isn't in this game
+    UnknownPlayer(Pid),
+    /// every seat is taken
+    Full,
+    /// the name {0:?} is already taken
+    NameTaken(String),
+    /// player {0} is already in this game
+    AlreadyRegistered(Pid),
+    /// a game can't have more than 255 players
+    TooMany,
+}
+
+/// One seat at the table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Player {
+    pub pid: Pid,
+    /// Who's sitting here, if anyone has taken the seat.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Roster {
+    players: SmallVec<[Player; 4]>,
+}
+
+impl Roster {
+    /// A roster of player_count empty seats, Pids 0 up to player_count.
+    pub fn new(player_count: u8) -> Roster {
+        Roster {
+            players: (0..player_count)
+                .map(|i| Player {
+                    pid: Pid(i),
+                    name: None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.players.is_empty()
+    }
+
+    /// Add an empty seat for a Pid.
+    pub fn register(&mut self, who: Pid) -> Result<(), RosterError> {
+        if self.contains(who) {
+            return Err(RosterError::AlreadyRegistered(who));
+        }
+        if self.len() >= u8::MAX as usize {
+            return Err(RosterError::TooMany);
+        }
+        self.players.push(Player {
+            pid: who,
+            name: None,
+        });
+        Ok(())
+    }
+
+    /// Whether a Pid is registered in this game.
+    pub fn contains(&self, who: Pid) -> bool {
+        self.get(who).is_some()
+    }
+
+    pub fn get(&self, who: Pid) -> Option<&Player> {
+        self.players.iter().find(|p| p.pid == who)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Player> {
+        self.players.iter()
+    }
+
+    /// The Pid of the seat taken by the given name.
+    pub fn find(&self, name: &str) -> Option<Pid> {
+        self.players
+            .iter()
+            .find(|p| p.name.as_deref() == Some(name))
+            .map(|p| p.pid)
+    }
+
+    /// Give the first free seat to a name, returning its Pid.
+    pub fn seat(&mut self, name: &str) -> Result<Pid, RosterError> {
+        if self.find(name).is_some() {
+            return Err(RosterError::NameTaken(name.into()));
+        }
+        let player = self
+            .players
+            .iter_mut()
+            .find(|p| p.name.is_none())
+            .ok_or(RosterError::Full)?;
+        player.name = Some(name.into());
+        Ok(player.pid)
+    }
+
+    /// Seat a name at a particular Pid, replacing whoever sat there.
+    pub fn seat_at(&mut self, who: Pid, name: &str) -> Result<(), RosterError> {
+        match self.find(name) {
+            Some(other) if other != who => return Err(RosterError::NameTaken(name.into())),
+            _ => {}
+        }
+        let player = self
+            .players
+            .iter_mut()
+            .find(|p| p.pid == who)
+            .ok_or(RosterError::UnknownPlayer(who))?;
+        player.name = Some(name.into());
+        Ok(())
+    }
+
+    /// Free up a seat, returning the name of whoever sat there.
+    pub fn unseat(&mut self, who: Pid) -> Result<Option<String>, RosterError> {
+        let player = self
+            .players
+            .iter_mut()
+            .find(|p| p.pid == who)
+            .ok_or(RosterError::UnknownPlayer(who))?;
+        Ok(player.name.take())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::{c, mv};
+    use crate::*;
+
+    #[test]
+    fn seating() {
+        let mut roster = Roster::new(2);
+        assert_eq!(roster.seat("ada"), Ok(Pid(0)));
+        assert_eq!(
+            roster.seat("ada"),
+            Err(RosterError::NameTaken("ada".into()))
+        );
+        assert_eq!(roster.seat("bob"), Ok(Pid(1)));
+        assert_eq!(roster.seat("cy"), Err(RosterError::Full));
+        assert_eq!(roster.find("bob"), Some(Pid(1)));
+
+        assert_eq!(roster.unseat(Pid(0)), Ok(Some("ada".into())));
+        assert_eq!(roster.seat("cy"), Ok(Pid(0)));
+        assert_eq!(
+            roster.seat_at(Pid(0), "bob"),
+            Err(RosterError::NameTaken("bob".into()))
+        );
+        assert_eq!(roster.seat_at(Pid(0), "dee"), Ok(()));
+        assert_eq!(roster.get(Pid(0)).unwrap().name.as_deref(), Some("dee"));
+        assert_eq!(
+            roster.unseat(Pid(2)),
+            Err(RosterError::UnknownPlayer(Pid(2)))
+        );
+    }
+
+    #[test]
+    fn registration() {
+        let mut game = Game::new(Board::stock_testing_empty(), 2, RuleSet::default()).unwrap();
+        assert_eq!(
+            game.roster.register(Pid(1)),
+            Err(RosterError::AlreadyRegistered(Pid(1)))
+        );
+        assert_eq!(game.roster.register(Pid(7)), Ok(()));
+        assert_eq!(game.player_count(), 3);
+        assert_eq!(game.roster.seat("ada"), Ok(Pid(0)));
+        assert_eq!(game.roster.seat_at(Pid(7), "bob"), Ok(()));
+
+        // Player 7 has a say, and the round waits for them.
+        let (_, ready) = game.propose_move(mv(0, c(0, 0), c(0, 1)));
+        assert!(!ready);
+        let (_, ready) = game.propose_move(mv(1, c(4, 4), c(4, 3)));
+        assert!(!ready);
+        assert_eq!(
+            game.propose_move(mv(7, c(1, 0), c(1, 1))),
+            (MoveFeedback::Committed, true)
+        );
+
+        let mut roster = Roster::new(0);
+        for i in 0..255 {
+            assert_eq!(roster.register(Pid(i)), Ok(()));
+        }
+        assert_eq!(roster.register(Pid(255)), Err(RosterError::TooMany));
+    }
+
+    #[test]
+    fn unknown_players_cannot_move() {
+        let mut game = Game::new(Board::stock_testing_empty(), 2, RuleSet::default()).unwrap();
+        let step = |who| mv(who, c(0, 0), c(0, 1));
+        assert_eq!(
+            game.propose_move(step(2)),
+            (MoveFeedback::UnknownPlayer, false)
+        );
+        assert_eq!(game.propose_move(step(255)).0, MoveFeedback::UnknownPlayer);
+        assert!(game.pending_moves.is_empty());
+        assert_eq!(game.round, RoundState::Fresh);
+        assert_eq!(game.propose_move(step(1)).0, MoveFeedback::Committed);
+    }
+}
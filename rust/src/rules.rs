@@ -0,0 +1,163 @@
+//! Rule variants, so that tables with their own house rules can play without forking the crate.
+//!
+//! `RuleSet::default()` is the README's canonical ruleset.
+
+use crate::*;
+use displaydoc::Display;
+
+/// Tie rule: {}
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TieRule {
+    /// the automaton moves along the column, or the last of the tied axes on a hex or hyper board
+    ColumnRule,
+    /// the automaton doesn't move
+    Freeze,
+}
+
+/// Win condition: {}
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WinCondition {
+    /// the first player whose goal the automaton ends a round on wins
+    Goal,
+    /// the first player whose goals the automaton has ended {0} rounds on wins
+    GoalRounds(u8),
+}
+
+/// A complete set of rule choices for a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RuleSet {
+    /// What the automaton does when its most urgent axes are equally urgent.
+    pub tie: TieRule,
+    /// Whether moving away from a repulsor is more urgent than moving toward an attractor. The
+    /// prototype had it the other way round until the "Frank correction", which the README
+    /// follows.
+    pub repulsor_first: bool,
+    /// Whether players proposing the very same move share it rather than conflict.
+    pub merge_identical_moves: bool,
+    pub win: WinCondition,
+    /// How each kind of piece moves.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub movement: PieceMovement,
+}
+
+impl Default for RuleSet {
+    fn default() -> RuleSet {
+        RuleSet {
+            tie: TieRule::ColumnRule,
+            repulsor_first: true,
+            merge_identical_moves: true,
+            win: WinCondition::Goal,
+            movement: PieceMovement::default(),
+        }
+    }
+}
+
+impl RuleSet {
+    /// The canonical rules, but freezing the automaton on ties instead of applying the column
+    /// rule.
+    pub fn freeze() -> RuleSet {
+        RuleSet {
+            tie: TieRule::Freeze,
+            ..RuleSet::default()
+        }
+    }
+}
+
+impl Game {
+    /// The player who wins now that the automatons have finished their step, if any.
+    ///
+    /// If automatons end up on several players' goals, the one that ends up first in order of
+    /// coordinate (by x, then y) decides, whichever stepped first. Must be called before the round
+    /// is added to the history.
+    pub(crate) fn check_win(&self) -> Option<Pid> {
+        let owner = |at: &Coord| self.goals.iter().find(|(c, _)| c == at).map(|g| g.1);
+        let who = self.board.automatons.iter().find_map(owner)?;
+        let needed = match self.rules.win {
+            WinCondition::Goal => return Some(who),
+            WinCondition::GoalRounds(n) => n as usize,
+        };
+        let earlier = self
+            .history
+            .iter()
+            .filter(|r| {
+                r.automatons_to
+                    .iter()
+                    .any(|&c| self.goals.contains(&(c, who)))
+            })
+            .count();
+        if earlier + 1 >= needed {
+            Some(who)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::{c, mv};
+    use crate::*;
+
+    fn game(rules: RuleSet) -> Game {
+        Game::new(Board::stock_testing_empty(), 2, rules).unwrap()
+    }
+
+    #[test]
+    fn attractor_first() {
+        let mut game = game(RuleSet::default());
+        let loc = game.board.automatons[0];
+        game.board.place(loc + Delta::XN * 2, Particle::Repulsor);
+        game.board.place(loc + Delta::YP * 2, Particle::Attractor);
+        assert_eq!(game.explain_automaton().to, loc + Delta::XP);
+
+        game.rules.repulsor_first = false;
+        assert_eq!(game.explain_automaton().to, loc + Delta::YP);
+    }
+
+    #[test]
+    fn identical_moves() {
+        let mut game = game(RuleSet::default());
+        game.board.place(c(0, 0), Particle::Attractor);
+        game.propose_move(mv(0, c(0, 0), c(0, 1)));
+        game.propose_move(mv(1, c(0, 0), c(0, 1)));
+        assert!(game.try_complete_round().is_ok());
+
+        game.rules.merge_identical_moves = false;
+        game.propose_move(mv(0, c(0, 1), c(0, 3)));
+        game.propose_move(mv(1, c(0, 1), c(0, 3)));
+        assert!(game.try_complete_round().is_err());
+        assert!(game.board.is_conflict(c(0, 1)));
+        assert!(game.board.is_conflict(c(0, 3)));
+    }
+
+    #[test]
+    fn goal_rounds() {
+        let mut game = game(RuleSet {
+            win: WinCondition::GoalRounds(2),
+            ..RuleSet::default()
+        });
+        // Hold the automaton in place between a pair of attractors, on player 1's goal.
+        let loc = game.board.automatons[0];
+        game.board.place(loc + Delta::XN * 2, Particle::Attractor);
+        game.board.place(loc + Delta::XP * 2, Particle::Attractor);
+        game.goals.push((loc, Pid(1)));
+
+        let round = |game: &mut Game| {
+            game.propose_move(mv(0, c(0, 0), c(0, 1)));
+            game.propose_move(mv(1, c(4, 4), c(4, 3)));
+            game.try_complete_round().unwrap();
+        };
+        round(&mut game);
+        assert_eq!(game.winner, None);
+        round(&mut game);
+        assert_eq!(game.winner, Some(Pid(1)));
+
+        assert!(game.undo_round());
+        assert_eq!(game.winner, None);
+        round(&mut game);
+        assert_eq!(game.winner, Some(Pid(1)));
+    }
+}
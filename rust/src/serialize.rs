@@ -0,0 +1,349 @@
+//! Serde support for the game state, behind the `serde` feature.
+//!
+//! The small types derive their representations directly. `Board` and `Game` go through explicit
+//! representation structs instead, so that the format stays stable as their internals change:
+//!
+//! - Both carry a `version` field, checked on the way in.
+//! - A board is written as its size, the automatons' locations, and its cells as a list of
+//!   columns (indexed `[x][y]`, like `Board::particles`). The conflict and passable lists are
+//!   rebuilt from the cell marks. Each cell is written as a struct of its particle, marks and
+//!   terrain, though it's packed into a byte in memory. A cell's terrain is left out when it's
+//!   plain floor, and so are the board's topology when it's flat and its tiling when it's square.
+//! - A game leaves out its event queue and the rounds it could redo, which are transient.
+
+use crate::*;
+use displaydoc::Display;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+
+/// Version of the serialized format for `Board` and `Game`.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Couldn't deserialize: {}
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    /// unsupported format version {0}
+    Version(u32),
+    /// the cells don't match the board size
+    Shape,
+    /// the automatons aren't where the board says
+    Automaton,
+    /// {0}
+    Inconsistent(BoardErrors),
+    /// the roster doesn't match the player count, or seats a player twice
+    Roster,
+}
+
+/// The serialized form of a `Cell`.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CellRepr {
+    what: Particle,
+    conflict: bool,
+    passable: bool,
+    #[serde(default, skip_serializing_if = "Terrain::is_floor")]
+    terrain: Terrain,
+}
+
+impl From<Cell> for CellRepr {
+    fn from(cell: Cell) -> CellRepr {
+        CellRepr {
+            what: cell.what(),
+            conflict: cell.conflict(),
+            passable: cell.passable(),
+            terrain: cell.terrain(),
+        }
+    }
+}
+
+impl From<CellRepr> for Cell {
+    fn from(repr: CellRepr) -> Cell {
+        let mut cell = Cell::new(repr.what).with_terrain(repr.terrain);
+        cell.set_conflict(repr.conflict);
+        cell.set_passable(repr.passable);
+        cell
+    }
+}
+
+#[derive(Serialize)]
+struct BoardOut<'a> {
+    version: u32,
+    size: Coord,
+    automatons: &'a [Coord],
+    cells: Vec<&'a [Cell]>,
+    #[serde(skip_serializing_if = "Topology::is_flat")]
+    topology: Topology,
+    #[serde(skip_serializing_if = "Tiling::is_square")]
+    tiling: Tiling,
+}
+
+#[derive(Deserialize)]
+struct BoardIn {
+    version: u32,
+    size: Coord,
+    automatons: Vec<Coord>,
+    cells: Vec<Vec<Cell>>,
+    #[serde(default)]
+    topology: Topology,
+    #[serde(default)]
+    tiling: Tiling,
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        BoardOut {
+            version: FORMAT_VERSION,
+            size: self.size,
+            automatons: &self.automatons,
+            cells: self
+                .particles
+                .outer_iter()
+                .map(|col| col.to_slice().expect("boards are in standard layout"))
+                .collect(),
+            topology: self.topology,
+            tiling: self.tiling,
+        }
+        .serialize(s)
+    }
+}
+
+impl TryFrom<BoardIn> for Board {
+    type Error = FormatError;
+
+    fn try_from(b: BoardIn) -> Result<Board, FormatError> {
+        if b.version != FORMAT_VERSION {
+            return Err(FormatError::Version(b.version));
+        }
+        let (w, h) = b.size.ix();
+        if b.cells.len() != w || b.cells.iter().any(|col| col.len() != h) {
+            return Err(FormatError::Shape);
+        }
+        let cells: Vec<Cell> = b.cells.into_iter().flatten().collect();
+        let particles = Grid::from_shape_vec((w, h), cells).map_err(|_| FormatError::Shape)?;
+
+        let mut board = Board {
+            particles,
+            size: b.size,
+            automatons: SmallVec::new(),
+            conflict_list: SmallVec::new(),
+            passable_list: SmallVec::new(),
+            topology: b.topology,
+            tiling: b.tiling,
+            index: LineIndex::default(),
+            hash: 0,
+        };
+        board.rebuild_lists();
+        if board.automatons.is_empty() || board.automatons.as_slice() != b.automatons.as_slice() {
+            return Err(FormatError::Automaton);
+        }
+        board.validate().map_err(FormatError::Inconsistent)?;
+        Ok(board)
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Board, D::Error> {
+        Board::try_from(BoardIn::deserialize(d)?).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Serialize)]
+struct GameOut<'a> {
+    version: u32,
+    board: &'a Board,
+    round: RoundState,
+    winner: Option<Pid>,
+    player_count: u8,
+    roster: &'a Roster,
+    rules: &'a RuleSet,
+    goals: &'a [(Coord, Pid)],
+    pending_moves: &'a [Move],
+    locked_players: &'a [Pid],
+    history: &'a [RoundHistory],
+}
+
+#[derive(Deserialize)]
+struct GameIn {
+    version: u32,
+    board: Board,
+    round: RoundState,
+    winner: Option<Pid>,
+    player_count: u8,
+    roster: Roster,
+    rules: RuleSet,
+    goals: Vec<(Coord, Pid)>,
+    pending_moves: Vec<Move>,
+    locked_players: Vec<Pid>,
+    history: Vec<RoundHistory>,
+}
+
+impl Serialize for Game {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        GameOut {
+            version: FORMAT_VERSION,
+            board: &self.board,
+            round: self.round,
+            winner: self.winner,
+            player_count: self.player_count(),
+            roster: &self.roster,
+            rules: &self.rules,
+            goals: &self.goals,
+            pending_moves: &self.pending_moves,
+            locked_players: &self.locked_players,
+            history: &self.history,
+        }
+        .serialize(s)
+    }
+}
+
+impl TryFrom<GameIn> for Game {
+    type Error = FormatError;
+
+    fn try_from(g: GameIn) -> Result<Game, FormatError> {
+        if g.version != FORMAT_VERSION {
+            return Err(FormatError::Version(g.version));
+        }
+        let player_count = g.player_count;
+        let roster = g.roster;
+        // A roster built up with `Roster::register` never seats a player twice.
+        let twice = roster
+            .iter()
+            .enumerate()
+            .any(|(i, p)| roster.iter().take(i).any(|q| q.pid == p.pid));
+        if roster.len() != player_count as usize || twice {
+            return Err(FormatError::Roster);
+        }
+        Ok(Game {
+            winner: g.winner,
+            locked_players: SmallVec::from_vec(g.locked_players),
+            board: g.board,
+            round: g.round,
+            pending_moves: SmallVec::from_vec(g.pending_moves),
+            goals: SmallVec::from_vec(g.goals),
+            roster,
+            rules: g.rules,
+            emit_events: false,
+            events: SmallVec::new(),
+            history: g.history,
+            undone: Vec::new(),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Game, D::Error> {
+        Game::try_from(GameIn::deserialize(d)?).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::{c, mv};
+    use crate::*;
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::fmt::Debug;
+
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(t: &T) {
+        let json = serde_json::to_string(t).unwrap();
+        let back: T = serde_json::from_str(&json).unwrap();
+        assert_eq!(&back, t, "{}", json);
+    }
+
+    fn mid_game() -> Game {
+        let mut game = Game::new(Board::stock_two_player(), 2, RuleSet::default()).unwrap();
+        game.goals.push((c(0, 0), Pid(0)));
+        game.goals.push((c(10, 10), Pid(1)));
+        game.roster.seat("ada").unwrap();
+        game.propose_move(mv(0, c(0, 0), c(0, 2)));
+        game.propose_move(mv(1, c(1, 0), c(1, 2)));
+        game.propose_move(mv(1, c(4, 0), c(2, 0)));
+        game.try_complete_round().unwrap();
+        game.propose_move(mv(0, c(0, 4), c(2, 4)));
+        game.propose_move(mv(1, c(1, 4), c(2, 4)));
+        assert!(game.try_complete_round().is_err());
+        game
+    }
+
+    #[test]
+    fn board_round_trip() {
+        round_trip(&Board::stock_two_player());
+        round_trip(&Board::stock_testing());
+        round_trip(&mid_game().board);
+        round_trip(&Board::hyper(&[3, 4, 2, 2]).unwrap());
+    }
+
+    #[test]
+    fn game_round_trip() {
+        round_trip(&Game::new(Board::stock_testing(), 2, RuleSet::freeze()).unwrap());
+        let mut game = Game::new(Board::stock_testing(), 2, RuleSet::default()).unwrap();
+        game.roster.register(Pid(7)).unwrap();
+        round_trip(&game);
+        let game = mid_game();
+        assert_eq!(game.round, RoundState::ResolvingConflict);
+        assert!(!game.board.conflict_list.is_empty());
+        round_trip(&game);
+    }
+
+    #[test]
+    fn feedback_round_trip() {
+        round_trip(&MoveFeedback::Committed);
+        round_trip(&MoveFeedback::SeeCoords(CoordsFeedback {
+            data: SmallVec::from_vec(vec![
+                (c(0, 0), CoordFeedback::Ok),
+                (c(9, 9), CoordFeedback::Oob),
+            ]),
+        }));
+        round_trip(&MoveResult::OccupiedAt(c(1, 2)));
+        round_trip(&RoundState::PartiallySubmitted);
+    }
+
+    #[test]
+    fn events_round_trip() {
+        let mut game = mid_game();
+        game.emit_events = true;
+        game.propose_move(mv(0, c(2, 4), c(3, 4)));
+        for event in game.drain_events() {
+            round_trip(&event);
+        }
+    }
+
+    #[test]
+    fn stable_format() {
+        let json = serde_json::to_value(Board::stock_testing_empty()).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["size"], serde_json::json!({"x": 5, "y": 5}));
+        assert_eq!(
+            json["cells"][2][2],
+            serde_json::json!({"what": "Automaton", "conflict": false, "passable": false})
+        );
+        let m = serde_json::to_value(mv(1, c(0, 0), c(0, 3))).unwrap();
+        assert_eq!(
+            m,
+            serde_json::json!({"who": 1, "from": {"x": 0, "y": 0}, "to": {"x": 0, "y": 3}})
+        );
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let mut json = serde_json::to_value(Board::stock_testing()).unwrap();
+        json["version"] = 2.into();
+        assert!(serde_json::from_value::<Board>(json).is_err());
+
+        let mut json = serde_json::to_value(Board::stock_testing()).unwrap();
+        json["automatons"] = serde_json::json!([{"x": 0, "y": 0}]);
+        assert!(serde_json::from_value::<Board>(json).is_err());
+
+        let mut json =
+            serde_json::to_value(Game::new(Board::stock_testing(), 2, RuleSet::default()).unwrap())
+                .unwrap();
+        json["roster"] = serde_json::json!([{"pid": 0, "name": "ada"}]);
+        assert!(serde_json::from_value::<Game>(json.clone()).is_err());
+        json["roster"] = serde_json::json!([{"pid": 3, "name": null}, {"pid": 3, "name": null}]);
+        assert!(serde_json::from_value::<Game>(json.clone()).is_err());
+        json.as_object_mut().unwrap().remove("roster");
+        assert!(serde_json::from_value::<Game>(json).is_err());
+
+        let mut json = serde_json::to_value(Board::stock_testing()).unwrap();
+        json["size"] = serde_json::json!({"x": 4, "y": 5});
+        assert!(serde_json::from_value::<Board>(json).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Goal assignment: the stock corner presets, and a setup phase for claiming corners.
+//!
+//! Per the README, in a two-player game each player owns two corners in the same row, and in a
+//! four-player game each player owns one corner. Either the corners are handed out up front
+//! (`Game::with_stock_goals`), or the game starts in `RoundState::Setup` and the players pick
+//! their own with `Game::claim_corner`.
+//!
+//! The Python prototype's `DEFAULT_GOALS` gave both players the corner (10, 0), which can't have
+//! been intended, so the two-player presets here follow the README instead.
+//!
+//! A torus has no corners, so there the goals go on the points a quarter of the way across the
+//! board each way instead (see `Board::goal_sites`), and are dealt out by the same rules.
+
+use crate::*;
+use displaydoc::Display;
+
+/// How many corners each player owns, for the player counts the README covers.
+fn corners_per_player(player_count: u8) -> Option<usize> {
+    match player_count {
+        2 => Some(2),
+        4 => Some(1),
+        _ => None,
+    }
+}
+
+/// Couldn't claim that corner: {}
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClaimError {
+    /// the game isn't being set up
+    NotSetup,
+    /// player {0} isn't in this game
+    UnknownPlayer(Pid),
+    /// {0} isn't a corner of the board (or a goal site, on a torus)
+    NotACorner(Coord),
+    /// it already belongs to player {0}
+    Taken(Pid),
+    /// you already have all your corners
+    AllClaimed,
+    /// your corners must be in the same row
+    NotSameRow,
+    /// player {0} already has a corner in that row
+    RowTaken(Pid),
+}
+
+impl Board {
+    /// The four corners, counterclockwise from the origin.
+    pub fn corners(&self) -> [Coord; 4] {
+        let (x, y) = (self.size.x - 1, self.size.y - 1);
+        [
+            Coord { x: 0, y: 0 },
+            Coord { x, y: 0 },
+            Coord { x, y },
+            Coord { x: 0, y },
+        ]
+    }
+
+    /// Where the goals go: the corners, or on a torus, the four points a quarter of the way
+    /// across from each other, again counterclockwise.
+    ///
+    /// A hex board (see `Board::hex`) has six corners; the goals go on the four that make two
+    /// opposite rows. On a hyper board (see `Board::hyper`), they're the corners of its folded
+    /// grid, at one end or the other of every axis after the first at once.
+    pub fn goal_sites(&self) -> [Coord; 4] {
+        match (self.tiling, self.topology) {
+            (Tiling::Hex, _) => {
+                let (x, y) = (self.size.x / 2, self.size.y / 2);
+                let n = std::cmp::min(x, y);
+                [
+                    Coord { x, y: y - n },
+                    Coord { x: x + n, y: y - n },
+                    Coord { x, y: y + n },
+                    Coord { x: x - n, y: y + n },
+                ]
+            }
+            (Tiling::Square, Topology::Flat) | (Tiling::Hyper(_), _) => self.corners(),
+            (Tiling::Square, Topology::Torus) => {
+                let (x0, y0) = (self.size.x / 4, self.size.y / 4);
+                let (x1, y1) = (self.size.x * 3 / 4, self.size.y * 3 / 4);
+                [
+                    Coord { x: x0, y: y0 },
+                    Coord { x: x1, y: y0 },
+                    Coord { x: x1, y: y1 },
+                    Coord { x: x0, y: y1 },
+                ]
+            }
+        }
+    }
+
+    /// The stock goals for the players of a two- or four-player roster, or None for any other
+    /// player count.
+    ///
+    /// With two players, the first in the roster owns the goal sites in the lower row (on a flat
+    /// board, the corners of the row y = 0) and the second those of the opposite row. With four
+    /// players, they go round the corners in roster order.
+    pub fn stock_goals(&self, roster: &Roster) -> Option<SmallVec<[(Coord, Pid); 4]>> {
+        let per_player = corners_per_player(roster.len() as u8)?;
+        let pids: SmallVec<[Pid; 4]> = roster.iter().map(|p| p.pid).collect();
+        let [c0, c1, c2, c3] = self.goal_sites();
+        let corners = if per_player == 2 {
+            [c0, c1, c3, c2]
+        } else {
+            [c0, c1, c2, c3]
+        };
+        Some(
+            corners
+                .iter()
+                .enumerate()
+                .map(|(i, &c)| (c, pids[i / per_player]))
+                .collect(),
+        )
+    }
+}
+
+impl Game {
+    /// Create a new game with the stock goals. Fails if there are none for this player count, or
+    /// if the board is inconsistent (see `Game::new`).
+    pub fn with_stock_goals(
+        board: Board,
+        player_count: u8,
+        rules: RuleSet,
+    ) -> Result<Game, BoardErrors> {
+        let mut game = Game::new(board, player_count, rules)?;
+        game.goals = game
+            .board
+            .stock_goals(&game.roster)
+            .ok_or(BoardError::PlayerCount(player_count))?;
+        Ok(game)
+    }
+
+    /// Create a new game in which the players claim their own corners before the first round.
+    /// Fails if the README has no corner rules for this player count, or if the board is
+    /// inconsistent.
+    pub fn with_setup(board: Board, player_count: u8, rules: RuleSet) -> Result<Game, BoardErrors> {
+        corners_per_player(player_count).ok_or(BoardError::PlayerCount(player_count))?;
+        let mut game = Game::new(board, player_count, rules)?;
+        game.round = RoundState::Setup;
+        Ok(game)
+    }
+
+    /// Claim a corner as one of a player's goals, during setup.
+    ///
+    /// Returns true once every player has all their corners, at which point the first round
+    /// starts.
+    #[instrument]
+    pub fn claim_corner(&mut self, who: Pid, at: Coord) -> Result<bool, ClaimError> {
+        use ClaimError::*;
+
+        if self.round != RoundState::Setup {
+            return Err(NotSetup);
+        }
+        let per_player = corners_per_player(self.player_count()).ok_or(NotSetup)?;
+        if !self.roster.contains(who) {
+            return Err(UnknownPlayer(who));
+        }
+        if !self.board.goal_sites().contains(&at) {
+            return Err(NotACorner(at));
+        }
+        if let Some(&(_, owner)) = self.goals.iter().find(|(c, _)| *c == at) {
+            return Err(Taken(owner));
+        }
+        let mut mine = self.goals.iter().filter(|(_, p)| *p == who);
+        if mine.clone().count() == per_player {
+            return Err(AllClaimed);
+        }
+        if mine.any(|(c, _)| c.y != at.y) {
+            return Err(NotSameRow);
+        }
+        // Sharing a row would leave one of the players without a whole row to claim.
+        if per_player > 1 {
+            let shared = self.goals.iter().find(|(c, p)| *p != who && c.y == at.y);
+            if let Some(&(_, owner)) = shared {
+                return Err(RowTaken(owner));
+            }
+        }
+
+        self.goals.push((at, who));
+        self.emit(GameEvent::CornerClaimed { who, at });
+        let done = self.goals.len() == per_player * self.player_count() as usize;
+        if done {
+            self.round = RoundState::Fresh;
+        }
+        Ok(done)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::{c, mv};
+    use crate::*;
+
+    #[test]
+    fn stock_goals() {
+        let board = Board::stock_two_player();
+        let game = Game::with_stock_goals(board.clone(), 2, RuleSet::default()).unwrap();
+        assert_eq!(
+            game.goals.as_slice(),
+            &[
+                (c(0, 0), Pid(0)),
+                (c(10, 0), Pid(0)),
+                (c(0, 10), Pid(1)),
+                (c(10, 10), Pid(1)),
+            ]
+        );
+        let goals = board.stock_goals(&Roster::new(4)).unwrap();
+        assert_eq!(goals[2], (c(10, 10), Pid(2)));
+        assert!(board.stock_goals(&Roster::new(3)).is_none());
+        let mut roster = Roster::new(1);
+        roster.register(Pid(7)).unwrap();
+        let goals = board.stock_goals(&roster).unwrap();
+        assert_eq!(goals[1], (c(10, 0), Pid(0)));
+        assert_eq!(goals[2], (c(0, 10), Pid(7)));
+        assert_eq!(
+            Game::with_setup(board, 3, RuleSet::default()).unwrap_err(),
+            BoardError::PlayerCount(3).into()
+        );
+        let errors = Game::with_stock_goals(Board::new(0, 5), 2, RuleSet::default()).unwrap_err();
+        assert!(errors.data.contains(&BoardError::Empty));
+    }
+
+    #[test]
+    fn claiming_corners() {
+        use ClaimError::*;
+        let mut game = Game::with_setup(Board::stock_two_player(), 2, RuleSet::default()).unwrap();
+        assert_eq!(game.round, RoundState::Setup);
+        let m = mv(0, c(0, 4), c(0, 3));
+        assert_eq!(game.propose_move(m).0, MoveFeedback::InSetup);
+
+        assert_eq!(game.claim_corner(Pid(0), c(0, 10)), Ok(false));
+        assert_eq!(game.claim_corner(Pid(1), c(0, 10)), Err(Taken(Pid(0))));
+        assert_eq!(
+            game.claim_corner(Pid(1), c(5, 10)),
+            Err(NotACorner(c(5, 10)))
+        );
+        assert_eq!(
+            game.claim_corner(Pid(2), c(0, 0)),
+            Err(UnknownPlayer(Pid(2)))
+        );
+        assert_eq!(game.claim_corner(Pid(0), c(10, 0)), Err(NotSameRow));
+        assert_eq!(game.claim_corner(Pid(1), c(10, 10)), Err(RowTaken(Pid(0))));
+        assert_eq!(game.claim_corner(Pid(0), c(10, 10)), Ok(false));
+        assert_eq!(game.claim_corner(Pid(0), c(0, 0)), Err(AllClaimed));
+        assert_eq!(game.claim_corner(Pid(1), c(10, 0)), Ok(false));
+        assert_eq!(game.claim_corner(Pid(1), c(0, 0)), Ok(true));
+
+        assert_eq!(game.round, RoundState::Fresh);
+        assert_eq!(game.claim_corner(Pid(1), c(0, 0)), Err(NotSetup));
+        assert_eq!(game.propose_move(m).0, MoveFeedback::Committed);
+    }
+
+    #[test]
+    fn rows_are_not_shared() {
+        use ClaimError::*;
+        let mut game = Game::with_setup(Board::stock_two_player(), 2, RuleSet::default()).unwrap();
+        assert_eq!(game.claim_corner(Pid(0), c(0, 0)), Ok(false));
+        // Had this gone through, player 0 would have had no corner left in its row.
+        assert_eq!(game.claim_corner(Pid(1), c(10, 0)), Err(RowTaken(Pid(0))));
+        assert_eq!(game.claim_corner(Pid(1), c(10, 10)), Ok(false));
+        assert_eq!(game.claim_corner(Pid(0), c(10, 0)), Ok(false));
+        assert_eq!(game.claim_corner(Pid(1), c(0, 10)), Ok(true));
+        assert_eq!(game.round, RoundState::Fresh);
+
+        // With four players, each has only one corner, so rows are shared.
+        let mut game = Game::with_setup(Board::stock_two_player(), 4, RuleSet::default()).unwrap();
+        assert_eq!(game.claim_corner(Pid(0), c(0, 0)), Ok(false));
+        assert_eq!(game.claim_corner(Pid(1), c(10, 0)), Ok(false));
+    }
+
+    #[test]
+    fn claims_are_recorded() {
+        let mut game = Game::with_setup(Board::stock_two_player(), 4, RuleSet::default()).unwrap();
+        game.emit_events = true;
+        let mut record = GameRecord::new(&game);
+        let corners = game.board.corners();
+        for (i, &corner) in corners.iter().rev().enumerate() {
+            game.claim_corner(Pid(i as u8), corner).unwrap();
+        }
+        for event in game.drain_events() {
+            record.observe(&event);
+        }
+        game.emit_events = false;
+        assert_eq!(record.replay().unwrap(), game);
+    }
+}
@@ -2,9 +2,25 @@ use crate::*;
 use smallvec::SmallVec;
 /// Player ID within a single game
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Pid(pub u8);
 
+impl core::fmt::Display for Pid {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct CoordsFeedback {
     pub data: SmallVec<[(Coord, CoordFeedback); 2]>,
 }
@@ -20,9 +36,10 @@ impl core::fmt::Display for CoordsFeedback {
 
 /// Coordinate on the board. TODO: microbenchmark different coord sizes
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Coord {
-    pub x: u8,
-    pub y: u8,
+    pub x: u16,
+    pub y: u16,
 }
 
 impl core::fmt::Display for Coord {
@@ -33,17 +50,18 @@ impl core::fmt::Display for Coord {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Delta {
-    dx: i8,
-    dy: i8,
+    pub(crate) dx: i32,
+    pub(crate) dy: i32,
 }
 
 impl std::ops::Sub for Coord {
     type Output = Delta;
 
+    /// The difference between two coordinates, which is always exact.
     fn sub(self, other: Coord) -> Delta {
         Delta {
-            dx: (self.x as i8 - other.x as i8),
-            dy: (self.y as i8 - other.y as i8),
+            dx: self.x as i32 - other.x as i32,
+            dy: self.y as i32 - other.y as i32,
         }
     }
 }
@@ -51,12 +69,10 @@ impl std::ops::Sub for Coord {
 impl std::ops::Add<Delta> for Coord {
     type Output = Coord;
 
+    /// Panics if the result isn't a valid coordinate; see `Coord::checked_add`.
     fn add(self, other: Delta) -> Coord {
-        Coord {
-            // TODO: Make this saturate rather than wrap
-            x: (self.x as i8 + other.dx) as u8,
-            y: (self.y as i8 + other.dy) as u8,
-        }
+        self.checked_add(other)
+            .expect("coordinate out of range in addition")
     }
 }
 
@@ -65,8 +81,8 @@ impl std::ops::Mul<isize> for Delta {
 
     fn mul(self, other: isize) -> Delta {
         Delta {
-            dx: self.dx * other as i8,
-            dy: self.dy * other as i8,
+            dx: self.dx * other as i32,
+            dy: self.dy * other as i32,
         }
     }
 }
@@ -75,52 +91,44 @@ impl Coord {
     pub fn ix(self) -> (usize, usize) {
         (self.x as usize, self.y as usize)
     }
+
+    /// Offset a coordinate, or None if either component would leave the range of a coordinate.
+    ///
+    /// This knows nothing of any board; see `Board::offset` for that.
+    pub fn checked_add(self, d: Delta) -> Option<Coord> {
+        let along = |at: u16, by: i32| std::convert::TryFrom::try_from(at as i64 + by as i64).ok();
+        Some(Coord {
+            x: along(self.x, d.dx)?,
+            y: along(self.y, d.dy)?,
+        })
+    }
 }
 
 impl Delta {
+    pub fn new(dx: i32, dy: i32) -> Delta {
+        Delta { dx, dy }
+    }
+
+    pub fn dx(self) -> i32 {
+        self.dx
+    }
+
+    pub fn dy(self) -> i32 {
+        self.dy
+    }
+
     pub(crate) const ZERO: Delta = Delta { dx: 0, dy: 0 };
     pub(crate) const XP: Delta = Delta { dx: 1, dy: 0 };
+    #[cfg(test)]
     pub(crate) const XN: Delta = Delta { dx: -1, dy: 0 };
     pub(crate) const YP: Delta = Delta { dx: 0, dy: 1 };
+    /// One step along a hex board's third axis (see `Tiling::Hex`).
+    pub(crate) const ZP: Delta = Delta { dx: 1, dy: -1 };
+    #[cfg(test)]
     pub(crate) const YN: Delta = Delta { dx: 0, dy: -1 };
     #[cfg(test)]
     pub(crate) const AXIAL_UNITS: [Delta; 4] = [Delta::XP, Delta::XN, Delta::YP, Delta::YN];
 
-    pub(crate) fn is_zero(self) -> bool {
-        self.dx == 0 && self.dy == 0
-    }
-
-    pub(crate) fn is_axial(self) -> bool {
-        self.dx == 0 || self.dy == 0 && !self.is_zero()
-    }
-
-    pub(crate) fn axial_unit(self) -> Delta {
-        if self.is_zero() {
-            Delta::ZERO
-        } else {
-            // Fencepost: prefer Y ("column rule"). This shouldn't be relied upon; in general, call
-            // this only on axial deltas.
-            if !self.is_axial() {
-                error!("{:?} is not an axial unit", self);
-            }
-            if self.dx.abs() > self.dy.abs() {
-                Delta {
-                    dx: self.dx.signum(),
-                    dy: 0,
-                }
-            } else {
-                Delta {
-                    dx: 0,
-                    dy: self.dy.signum(),
-                }
-            }
-        }
-    }
-
-    pub(crate) fn displacement(self) -> usize {
-        self.dx.abs() as usize + self.dy.abs() as usize
-    }
-
     #[cfg(test)]
     pub(crate) fn perpendicular(self) -> Delta {
         Delta {
@@ -131,30 +139,149 @@ impl Delta {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Move {
     pub who: Pid,
     pub from: Coord,
     pub to: Coord,
 }
 
+/// What a particle does to an automaton that sees it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Force {
+    Attract,
+    Repel,
+    /// Nothing, like the edge of the board.
+    Nothing,
+}
+
 impl Particle {
+    /// Every kind of particle, in declaration order, so that `ALL[p as usize] == p`.
+    pub(crate) const ALL: [Particle; 10] = [
+        Particle::Repulsor,
+        Particle::Attractor,
+        Particle::Automaton,
+        Particle::Vacuum,
+        Particle::Blocker,
+        Particle::RowAttractor,
+        Particle::ColumnAttractor,
+        Particle::RowRepulsor,
+        Particle::ColumnRepulsor,
+        Particle::Mirror,
+    ];
+
     pub(crate) fn is_vacuum(self) -> bool {
         self == Particle::Vacuum
     }
+
+    /// The force the particle exerts on an automaton that sees it along an axis (0 for the row, 1
+    /// for the column).
+    ///
+    /// Other automatons are walls, just like the edge of the board.
+    pub fn force(self, axis: usize) -> Force {
+        use Particle::*;
+        match (self, axis) {
+            (Attractor, _) | (RowAttractor, 0) | (ColumnAttractor, 1) => Force::Attract,
+            (Repulsor, _) | (RowRepulsor, 0) | (ColumnRepulsor, 1) => Force::Repel,
+            _ => Force::Nothing,
+        }
+    }
+
+    /// The axis along which a gaze carries on past the particle, having reached it along the
+    /// given one, or None if it stops here.
+    ///
+    /// Mirrors only turn gazes between the row and the column; anything else stops at them.
+    pub fn pass(self, axis: usize) -> Option<usize> {
+        match (self, axis) {
+            (Particle::Vacuum, _) => Some(axis),
+            (Particle::Mirror, 0) => Some(1),
+            (Particle::Mirror, 1) => Some(0),
+            _ => None,
+        }
+    }
+}
+
+impl Topology {
+    /// `Board::offset`, on any board of the given size.
+    pub(crate) fn offset(self, size: Coord, c: Coord, d: Delta) -> Option<Coord> {
+        let along = |at: u16, by: i32, size: u16| {
+            let to = at as i64 + by as i64;
+            match self {
+                Topology::Flat if (0..size as i64).contains(&to) => Some(to as u16),
+                Topology::Flat => None,
+                Topology::Torus => Some(to.rem_euclid(size as i64) as u16),
+            }
+        };
+        Some(Coord {
+            x: along(c.x, d.dx, size.x)?,
+            y: along(c.y, d.dy, size.y)?,
+        })
+    }
+
+    /// `Board::displacement`, on any board of the given size.
+    pub(crate) fn displacement(self, size: Coord, from: Coord, to: Coord) -> Delta {
+        let along = |from: u16, to: u16, size: u16| {
+            let d = to as i32 - from as i32;
+            match self {
+                Topology::Flat => d,
+                Topology::Torus => {
+                    let d = d.rem_euclid(size as i32);
+                    if d * 2 > size as i32 {
+                        d - size as i32
+                    } else {
+                        d
+                    }
+                }
+            }
+        };
+        Delta {
+            dx: along(from.x, to.x, size.x),
+            dy: along(from.y, to.y, size.y),
+        }
+    }
 }
 
 impl Cell {
     pub(crate) fn occludes(&self) -> bool {
-        // Vacuum can always be passed through, non-vacuum if passable is set.
-        !(self.what.is_vacuum() || self.passable)
+        // Vacuum can always be passed through, non-vacuum if passable is set. Walls never can.
+        self.terrain() == Terrain::Wall || !(self.what().is_vacuum() || self.passable())
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct Raycast {
-    pub(crate) what: Particle,
-    pub(crate) hit: Option<Coord>,
-    pub(crate) dist: usize,
+/// The nearest particle in some direction, as the automaton sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Raycast {
+    /// What was hit, or Vacuum if the ray left the board.
+    pub what: Particle,
+    /// Where it was hit, or None if the ray left the playable area or ran into a wall.
+    pub hit: Option<Coord>,
+    /// How many steps the ray took, counting the one that hit (or left the board).
+    pub dist: usize,
+    /// The axis the ray was travelling along when it stopped, which mirrors may have changed.
+    pub axis: usize,
+    /// How many empty cells the ray crossed before reaching anything, mirrors included.
+    pub clear: usize,
+}
+
+/// What the automaton makes of a ray.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Sighting {
+    pub force: Force,
+    pub dist: usize,
+    /// Whether the automaton could step toward the ray's end.
+    pub room: bool,
+}
+
+impl Raycast {
+    pub(crate) fn sighting(&self) -> Sighting {
+        Sighting {
+            force: self.what.force(self.axis),
+            dist: self.dist,
+            room: self.clear > 0,
+        }
+    }
 }
 
 // Program invariant: by the time a coord ever hits a Board method (besides
@@ -163,181 +290,188 @@ pub(crate) struct Raycast {
 impl Board {
     /// Standard board layout for a two player game.
     pub fn stock_two_player() -> Board {
-        let o = Cell {
-            what: Particle::Vacuum,
-            conflict: false,
-            passable: false,
-        };
-        let r = Cell {
-            what: Particle::Repulsor,
-            ..o
-        };
-        let a = Cell {
-            what: Particle::Attractor,
-            ..o
-        };
-        let d = Cell {
-            what: Particle::Automaton,
-            ..o
-        };
-        Board {
-            particles: arr2(&[
-                [r, r, o, o, r, r, r, o, o, r, r],
-                [o, o, o, a, r, r, r, a, o, o, o],
-                [o, o, o, o, o, o, o, o, o, o, o],
-                [o, o, o, o, o, o, o, o, o, o, o],
-                [a, a, o, o, o, o, o, o, o, a, a],
-                [r, r, o, o, o, d, o, o, o, r, r],
-                [a, a, o, o, o, o, o, o, o, a, a],
-                [o, o, o, o, o, o, o, o, o, o, o],
-                [o, o, o, o, o, o, o, o, o, o, o],
-                [o, o, o, a, r, r, r, a, o, o, o],
-                [r, r, o, o, r, r, r, o, o, r, r],
-            ]),
-            size: Coord { x: 11, y: 11 },
-            automaton_location: Coord { x: 5, y: 5 },
-            conflict_list: SmallVec::new(),
-            passable_list: SmallVec::new(),
-        }
+        let o = Cell::new(Particle::Vacuum);
+        let r = Cell::new(Particle::Repulsor);
+        let a = Cell::new(Particle::Attractor);
+        let d = Cell::new(Particle::Automaton);
+        Board::from_grid(arr2(&[
+            [r, r, o, o, r, r, r, o, o, r, r],
+            [o, o, o, a, r, r, r, a, o, o, o],
+            [o, o, o, o, o, o, o, o, o, o, o],
+            [o, o, o, o, o, o, o, o, o, o, o],
+            [a, a, o, o, o, o, o, o, o, a, a],
+            [r, r, o, o, o, d, o, o, o, r, r],
+            [a, a, o, o, o, o, o, o, o, a, a],
+            [o, o, o, o, o, o, o, o, o, o, o],
+            [o, o, o, o, o, o, o, o, o, o, o],
+            [o, o, o, a, r, r, r, a, o, o, o],
+            [r, r, o, o, r, r, r, o, o, r, r],
+        ]))
     }
 
     /// 5x5 board with an automaton and some particles.
     pub fn stock_testing() -> Board {
-        let o = Cell {
-            what: Particle::Vacuum,
-            conflict: false,
-            passable: false,
-        };
-        let r = Cell {
-            what: Particle::Repulsor,
-            ..o
-        };
-        let a = Cell {
-            what: Particle::Attractor,
-            ..o
-        };
-        let d = Cell {
-            what: Particle::Automaton,
-            ..o
-        };
-        Board {
-            particles: arr2(&[
-                [r, o, a, o, r],
-                [o, o, o, o, o],
-                [r, o, d, o, r],
-                [o, o, o, o, o],
-                [r, o, a, o, r],
-            ]),
-            size: Coord { x: 5, y: 5 },
-            automaton_location: Coord { x: 2, y: 2 },
-            conflict_list: SmallVec::new(),
-            passable_list: SmallVec::new(),
-        }
+        let o = Cell::new(Particle::Vacuum);
+        let r = Cell::new(Particle::Repulsor);
+        let a = Cell::new(Particle::Attractor);
+        let d = Cell::new(Particle::Automaton);
+        Board::from_grid(arr2(&[
+            [r, o, a, o, r],
+            [o, o, o, o, o],
+            [r, o, d, o, r],
+            [o, o, o, o, o],
+            [r, o, a, o, r],
+        ]))
     }
 
     /// Empty 5x5 board containing a lonely automaton.
     pub fn stock_testing_empty() -> Board {
-        let o = Cell {
-            what: Particle::Vacuum,
-            conflict: false,
-            passable: false,
-        };
-        let d = Cell {
-            what: Particle::Automaton,
-            ..o
-        };
-        Board {
-            particles: arr2(&[
-                [o, o, o, o, o],
-                [o, o, o, o, o],
-                [o, o, d, o, o],
-                [o, o, o, o, o],
-                [o, o, o, o, o],
-            ]),
-            size: Coord { x: 5, y: 5 },
-            automaton_location: Coord { x: 2, y: 2 },
-            conflict_list: SmallVec::new(),
-            passable_list: SmallVec::new(),
-        }
-    }
-
-    /// Place a particle on the board.
+        let o = Cell::new(Particle::Vacuum);
+        let d = Cell::new(Particle::Automaton);
+        Board::from_grid(arr2(&[
+            [o, o, o, o, o],
+            [o, o, o, o, o],
+            [o, o, d, o, o],
+            [o, o, o, o, o],
+            [o, o, o, o, o],
+        ]))
+    }
+
+    /// Place a particle on the board, replacing whatever was there.
     ///
-    /// If the particle isn't the automaton, this increases the matter on the
-    /// board. If it is the automaton, it is forcibly moved from wherever else it
-    /// is on the board, leaving a vacuum in its place. (This restriction might
-    /// later be lifted to allow more than one automaton on the board, but this
-    /// method is unlikely to abide such a change.)
+    /// Placing an automaton adds another one to the board. Take care not to replace the last
+    /// automaton with anything else, since the game needs at least one. Particles can only be
+    /// placed on floor.
     pub fn place(&mut self, c: Coord, w: Particle) {
+        debug_assert!(w.is_vacuum() || self.particles[c.ix()].terrain() == Terrain::Floor);
+        self.change(c, |cell| cell.set_what(w));
+        self.automatons.retain(|a| *a != c);
         if w == Particle::Automaton {
-            self.particles[self.automaton_location.ix()].what = Particle::Vacuum;
-            self.automaton_location = c;
+            self.automatons.push(c);
+            self.sort_automatons();
         }
-        self.particles[c.ix()].what = w;
+    }
+
+    /// Change what's on a cell, keeping the line index and the hash up to date.
+    fn change(&mut self, c: Coord, f: impl FnOnce(&mut Cell)) {
+        let cell = &mut self.particles[c.ix()];
+        self.hash ^= zobrist::cell_key(c, *cell);
+        f(cell);
+        self.hash ^= zobrist::cell_key(c, *cell);
+        self.index.update(c, *cell);
+    }
+
+    fn sort_automatons(&mut self) {
+        self.automatons.sort_unstable_by_key(|c| (c.x, c.y));
     }
 
     /// Mark a coordinate as passable, because some move specifies it as a source.
     pub(crate) fn mark_passable(&mut self, c: Coord) {
-        self.particles[c.ix()].passable = true;
+        self.particles[c.ix()].set_passable(true);
         self.passable_list.push(c);
     }
 
-    /// Forcibly swap two positions on the board.
+    /// Clear the passable mark on a cell, because the piece on it has stopped moving.
+    pub(crate) fn settle(&mut self, c: Coord) {
+        self.particles[c.ix()].set_passable(false);
+    }
+
+    /// Forcibly swap the particles at two positions on the board.
     ///
-    /// Conserves total particle counts. This can also do weird, probably
-    /// illogical things, like swapping conflict flags. This method also does
-    /// absolutely no bounds checking, and thus can panic if the coordinate is
-    /// out of bounds.
+    /// Conserves total particle counts. Conflict and passable marks stay with
+    /// their cells. This method also does absolutely no bounds checking, and
+    /// thus can panic if the coordinate is out of bounds.
     pub(crate) fn force_move(&mut self, from: Coord, to: Coord) {
-        self.particles.swap(from.ix(), to.ix());
-
-        if self.automaton_location == from {
-            debug_assert_eq!(self.particles[to.ix()].what, Particle::Automaton);
-            self.automaton_location = to;
+        let (a, b) = (
+            self.particles[from.ix()].what(),
+            self.particles[to.ix()].what(),
+        );
+        self.change(from, |cell| cell.set_what(b));
+        self.change(to, |cell| cell.set_what(a));
+
+        let mut moved = false;
+        for a in &mut self.automatons {
+            if *a == from || *a == to {
+                *a = if *a == from { to } else { from };
+                moved = true;
+            }
+        }
+        if moved {
+            self.sort_automatons();
         }
     }
 
     /// Raycast on the board down an axis from a coordinate.
     ///
-    /// The ray starts from, but does not include, the "from" coordinate.
+    /// The ray starts from, but does not include, the "from" coordinate, and is turned by any
+    /// mirrors it meets. A ray that comes back round to its start stops there, seeing the
+    /// automaton. That's also what stops rays on a torus going round for ever: the path of a ray
+    /// can be traced backward as well as forward, so it must either stop or come back round.
+    /// Along rows and columns, the ray skips over empty floor using the board's line index.
     ///
-    /// The axis SHOULD be a unit vector, but any nonzero Delta is acceptable. This method only
-    /// tests the integer multiples of that offset, and relies on the ray eventually containing
-    /// out-of-bounds points to terminate.
-    ///
-    /// The raycast's dist field is set to the integer at which iteration terminated. If iteration
-    /// terminated in-bounds, this is guaranteed to be on a non-Vacuum particle. If it terminated
-    /// out-of-bounds, i is the first integer multiple of axis that is out of bounds (and the
-    /// particle is Vacuum). (These facts are depended upon in the automaton's reasoning; see
-    /// evaluate_axis.)
+    /// The axis must be a unit vector. The raycast's dist field is set to the number of steps
+    /// taken. If the ray stopped in-bounds, this is guaranteed to be on a non-Vacuum particle. If
+    /// it left the board, the last step was the first out of bounds (and the particle is Vacuum).
+    /// (These facts are depended upon in the automaton's reasoning; see evaluate_axis.)
     #[instrument]
-    pub(crate) fn raycast(&self, from: Coord, axis: Delta) -> Raycast {
-        debug_assert_ne!(axis, Delta::ZERO);
-
-        for i in 1isize.. {
-            let co = from + axis * i;
-            if !self.inbounds(co) {
-                return Raycast {
-                    what: Particle::Vacuum,
-                    hit: None,
-                    dist: i as usize,
-                };
+    pub fn raycast(&self, from: Coord, axis: Delta) -> Raycast {
+        debug_assert_eq!(self.tiling.distance(axis), 1);
+
+        let index = |d: Delta| self.tiling.axis_of(d).expect("rays run along an axis");
+        let (mut co, mut d, mut clear, mut dist) = (from, axis, None, 0);
+        loop {
+            // Skip straight over any empty floor, where the index can see the way.
+            if let Some(run) = self.index.empty_run(self.size, self.topology, co, d, from) {
+                co = self
+                    .offset(co, d * run as isize)
+                    .expect("empty floor is on the board");
+                dist += run;
             }
-            let c = self.particles[co.ix()];
-            /* NB: The following condition could also be `c.occludes()`, but this is almost
-             * certainly being called within Board::automaton_move, and by this time the marks
+            dist += 1;
+
+            // Walls look just like the edge of the board.
+            let next = self
+                .offset(co, d)
+                .filter(|&c| self.inbounds(c) && !self.is_wall(c));
+            co = match next {
+                Some(c) => c,
+                None => {
+                    return Raycast {
+                        what: Particle::Vacuum,
+                        hit: None,
+                        dist,
+                        axis: index(d),
+                        clear: clear.unwrap_or(dist - 1),
+                    }
+                }
+            };
+            /* NB: The following could also check `c.occludes()`, but this is almost certainly
+             * being called while working out the automaton's move, and by this time the marks
              * should be clear anyway.
              */
-            if !c.what.is_vacuum() {
-                return Raycast {
-                    what: c.what,
-                    hit: Some(co),
-                    dist: i as usize,
-                };
+            let what = if co == from {
+                Particle::Automaton
+            } else {
+                self.particles[co.ix()].what()
+            };
+            if !what.is_vacuum() && clear.is_none() {
+                clear = Some(dist - 1);
+            }
+            match what.pass(index(d)) {
+                Some(0) if d.dx == 0 => d = Delta { dx: d.dy, dy: 0 },
+                Some(1) if d.dy == 0 => d = Delta { dx: 0, dy: d.dx },
+                Some(_) => {}
+                None => {
+                    return Raycast {
+                        what,
+                        hit: Some(co),
+                        dist,
+                        axis: index(d),
+                        clear: clear.unwrap_or(dist - 1),
+                    }
+                }
             }
         }
-
-        unreachable!()
     }
 
     /// Mark a cell as conflicted.
@@ -349,19 +483,46 @@ impl Board {
     /// indicate that two plebeians attempted to move the same particle differently, or move
     /// different particles to the same cell. When conflict resolution ends, the marks are cleared.
     pub(crate) fn mark_conflict(&mut self, c: Coord) {
-        self.particles[c.ix()].conflict = true;
+        self.particles[c.ix()].set_conflict(true);
         self.conflict_list.push(c);
     }
 
+    /// Rebuild the lists of automatons and marked cells, the line index and the hash, from the
+    /// cells.
+    ///
+    /// Only needed when the cells were filled in directly, as when parsing or deserializing.
+    pub(crate) fn rebuild_lists(&mut self) {
+        self.index = LineIndex::new(&self.particles);
+        self.hash = zobrist::hash_cells(&self.particles);
+        self.automatons.clear();
+        self.conflict_list.clear();
+        self.passable_list.clear();
+        for x in 0..self.size.x {
+            for y in 0..self.size.y {
+                let c = Coord { x, y };
+                let cell = self.particles[c.ix()];
+                if cell.what() == Particle::Automaton {
+                    self.automatons.push(c);
+                }
+                if cell.conflict() {
+                    self.conflict_list.push(c);
+                }
+                if cell.passable() {
+                    self.passable_list.push(c);
+                }
+            }
+        }
+    }
+
     /// Clear all conflict/passable marks.
     ///
     /// This is done at the end of conflict resolution (RoundState::ResolvingConflict).
     pub(crate) fn clear_marks(&mut self) {
         for c in self.conflict_list.drain(..) {
-            self.particles[c.ix()].conflict = false;
+            self.particles[c.ix()].set_conflict(false);
         }
         for c in self.passable_list.drain(..) {
-            self.particles[c.ix()].passable = false;
+            self.particles[c.ix()].set_passable(false);
         }
     }
 
@@ -370,54 +531,102 @@ impl Board {
     /// If this is true, the cell may not be specified as a source or destination of any move
     /// (MoveError::Conflicted).
     pub(crate) fn is_conflict(&self, c: Coord) -> bool {
-        self.particles[c.ix()].conflict
+        self.particles[c.ix()].conflict()
     }
 
     /// Test whether the addressed cell is vacuum.
     pub(crate) fn is_vacuum(&self, c: Coord) -> bool {
-        self.particles[c.ix()].what.is_vacuum()
+        self.particles[c.ix()].what().is_vacuum()
     }
 
-    /// Test whether the addressed cell is the automaton.
+    /// Test whether the addressed cell is an automaton.
     pub(crate) fn is_automaton(&self, c: Coord) -> bool {
-        self.automaton_location == c
+        self.automatons.contains(&c)
+    }
+
+    /// Test whether the addressed cell is a wall.
+    pub(crate) fn is_wall(&self, c: Coord) -> bool {
+        self.particles[c.ix()].terrain() == Terrain::Wall
+    }
+
+    /// Whether a move may start or end at a coordinate, and if not, why not.
+    pub(crate) fn coord_feedback(&self, c: Coord) -> CoordFeedback {
+        use CoordFeedback::*;
+        if !self.inbounds(c) {
+            Oob
+        } else if self.is_automaton(c) {
+            Automaton
+        } else if self.is_wall(c) {
+            Wall
+        } else if self.is_conflict(c) {
+            Conflict
+        } else {
+            Ok
+        }
     }
 
-    /// Test whether the coordinate is within the boundaries of the board.
+    /// Test whether the coordinate is within the playable area of the board: inside its
+    /// boundaries, and not over a hole.
     ///
     /// It is illegal to specify an out-of-bounds coordinate as the source or destination of a move
     /// (MoveError::Oob).
     pub(crate) fn inbounds(&self, c: Coord) -> bool {
-        c.x < self.size.x && c.y < self.size.y
+        c.x < self.size.x && c.y < self.size.y && self.particles[c.ix()].terrain() != Terrain::Hole
+    }
+
+    /// The coordinate a delta away from another, wrapping around a torus, or None if that's off
+    /// the edge of a flat board. Holes aren't checked for.
+    pub fn offset(&self, c: Coord, d: Delta) -> Option<Coord> {
+        self.topology.offset(self.size, c, d)
+    }
+
+    /// The delta that takes one coordinate to another. On a torus, that's the shorter way round
+    /// on each axis, or the positive way if both are the same length.
+    pub fn displacement(&self, from: Coord, to: Coord) -> Delta {
+        self.topology.displacement(self.size, from, to)
+    }
+
+    /// Change the terrain of a cell, which must be empty.
+    ///
+    /// This is for designing boards before a game starts; terrain never changes during one.
+    pub fn set_terrain(&mut self, c: Coord, terrain: Terrain) {
+        debug_assert!(self.particles[c.ix()].what().is_vacuum());
+        self.change(c, |cell| cell.set_terrain(terrain));
     }
 }
 
 impl AutomatonDecision {
-    pub(crate) fn priority(&self) -> usize {
+    pub(crate) fn priority(&self, rules: &RuleSet) -> usize {
         use AutomatonDecision::*;
+        // "Frank correction": FromRepulsor is normally the higher priority
+        let (attractor, repulsor) = if rules.repulsor_first {
+            (10, 20)
+        } else {
+            (20, 10)
+        };
         match self {
             None => 0,
-            TowardAttractor { .. } => 10,
-            // "Frank correction": this is higher priority
-            FromRepulsor { .. } => 20,
+            TowardAttractor { .. } => attractor,
+            FromRepulsor { .. } => repulsor,
             UnbalancedPair { .. } => 30,
         }
     }
 
-    pub(crate) fn delta(&self, axis: Delta) -> Delta {
+    /// Whether the decision is to move in the positive direction, or None if it's to stay put.
+    pub fn direction(&self) -> Option<bool> {
         use AutomatonDecision::*;
-        fn sgn(&b: &bool) -> isize {
-            if b {
-                1
-            } else {
-                -1
+        match *self {
+            UnbalancedPair { pos, .. } | FromRepulsor { pos, .. } | TowardAttractor { pos, .. } => {
+                Some(pos)
             }
+            None => Option::None,
         }
+    }
 
-        match self {
-            UnbalancedPair { pos, .. } | FromRepulsor { pos, .. } | TowardAttractor { pos, .. } => {
-                axis * sgn(pos)
-            }
+    pub(crate) fn delta(&self, axis: Delta) -> Delta {
+        match self.direction() {
+            Some(true) => axis,
+            Some(false) => axis * -1,
             None => Delta::ZERO,
         }
     }
@@ -437,10 +646,34 @@ impl PartialEq for AutomatonDecision {
 
 impl Eq for AutomatonDecision {}
 
+impl core::fmt::Debug for Board {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        if self.topology == Topology::Torus {
+            write!(f, "toroidal ")?;
+        }
+        match self.tiling {
+            Tiling::Square => write!(f, "{}x{} board", self.size.x, self.size.y)?,
+            Tiling::Hex => write!(f, "hexagonal {}x{} board", self.size.x, self.size.y)?,
+            Tiling::Hyper(shape) => write!(f, "{} hyper board", shape)?,
+        }
+        write!(f, " with automaton")?;
+        if self.automatons.len() != 1 {
+            write!(f, "s")?;
+        }
+        for (i, c) in self.automatons.iter().enumerate() {
+            write!(f, "{} {}", if i == 0 { " at" } else { "," }, c)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::testutil::{c, mv};
     use crate::*;
 
+    // Only ever read through Debug, when a test fails.
+    #[allow(dead_code)]
     #[derive(Debug)]
     struct AutMoveError {
         board: Board,
@@ -448,30 +681,30 @@ mod tests {
         actual_move: Delta,
     }
 
-    type AutMoveTest = Result<(), AutMoveError>;
+    type AutMoveTest = Result<(), Box<AutMoveError>>;
 
     fn expect_automaton_move(game: &mut Game, by: Delta) -> AutMoveTest {
-        let t0 = game.board.automaton_location;
+        let t0 = game.board.automatons[0];
         let d = game.automaton_move() - t0;
         if d == by {
             Ok(())
         } else {
-            Err(AutMoveError {
+            Err(Box::new(AutMoveError {
                 board: game.board.clone(),
                 expected_move: by,
                 actual_move: d,
-            })
+            }))
         }
     }
 
     fn testing_game() -> Game {
-        Game::new(Board::stock_testing_empty(), 2, true)
+        Game::new(Board::stock_testing_empty(), 2, RuleSet::default()).unwrap()
     }
 
     #[test]
     fn automaton_stays_put() -> AutMoveTest {
         let board = Board::stock_two_player();
-        let mut game = Game::new(board, 2, true);
+        let mut game = Game::new(board, 2, RuleSet::default()).unwrap();
         expect_automaton_move(&mut game, Delta::ZERO)
     }
 
@@ -479,7 +712,7 @@ mod tests {
     fn unbalanced_pair() -> AutMoveTest {
         for &d in Delta::AXIAL_UNITS.iter() {
             let mut game = testing_game();
-            let loc = game.board.automaton_location;
+            let loc = game.board.automatons[0];
             game.board.place(loc + d * 2, Particle::Attractor);
             game.board.place(loc + d * (-2), Particle::Repulsor);
             println!("* empty UnP delta {:?}", d);
@@ -511,7 +744,7 @@ mod tests {
     fn unbalanced_pair_limits() -> AutMoveTest {
         for &d in Delta::AXIAL_UNITS.iter() {
             let mut game = testing_game();
-            let loc = game.board.automaton_location;
+            let loc = game.board.automatons[0];
 
             let clean_board = game.board.clone();
 
@@ -534,7 +767,7 @@ mod tests {
     fn repulsor() -> AutMoveTest {
         for &d in Delta::AXIAL_UNITS.iter() {
             let mut game = testing_game();
-            let loc = game.board.automaton_location;
+            let loc = game.board.automatons[0];
             let perp = d.perpendicular();
 
             let try_with_attractors = |g: &mut Game, e: Delta| -> AutMoveTest {
@@ -570,15 +803,785 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn explain_unbalanced_pair() {
+        let mut game = testing_game();
+        let loc = game.board.automatons[0];
+        game.board.place(loc + Delta::XP * 2, Particle::Attractor);
+        game.board.place(loc + Delta::XN, Particle::Repulsor);
+        game.board.place(loc + Delta::YP * 2, Particle::Attractor);
+
+        let why = game.explain_automaton();
+        assert_eq!(why.to, loc + Delta::XP);
+        assert_eq!(why.choice, AxisChoice::Urgent(0));
+        assert_eq!(
+            why.axes[0].rays.0,
+            Raycast {
+                what: Particle::Attractor,
+                hit: Some(loc + Delta::XP * 2),
+                dist: 2,
+                axis: 0,
+                clear: 1,
+            }
+        );
+        assert_eq!(why.axes[0].rays.1.dist, 1);
+        assert_eq!(why.axes[1].rays.1.hit, None);
+        assert!(matches!(
+            why.axes[0].decision,
+            AutomatonDecision::UnbalancedPair {
+                pos: true,
+                att_dist: 2,
+                rep_dist: 1,
+            }
+        ));
+        assert!(matches!(
+            why.axes[1].decision,
+            AutomatonDecision::TowardAttractor {
+                pos: true,
+                att_dist: 2,
+            }
+        ));
+    }
+
+    #[test]
+    fn explain_tied_axes() {
+        let mut game = testing_game();
+        let loc = game.board.automatons[0];
+        assert_eq!(game.explain_automaton().choice, AxisChoice::Neither);
+
+        game.board.place(loc + Delta::XN * 2, Particle::Attractor);
+        game.board.place(loc + Delta::YN * 2, Particle::Attractor);
+        let why = game.explain_automaton();
+        assert_eq!(why.choice, AxisChoice::ColumnRule(1));
+        assert_eq!(why.to, loc + Delta::YN);
+
+        game.rules.tie = TieRule::Freeze;
+        let why = game.explain_automaton();
+        assert_eq!(why.choice, AxisChoice::Freeze);
+        assert_eq!(why.to, loc);
+        assert_eq!(
+            why.to_string(),
+            "Along the row: toward the attractor 2 away. Along the column: toward the attractor 2 \
+             away. Went with neither, since the axes tied and it freezes instead of applying the \
+             column rule, staying at (2, 2)."
+        );
+    }
+
+    #[test]
+    fn experimental_particles() {
+        let explain = |notation: &str| {
+            let game = Game::new(notation.parse().unwrap(), 2, RuleSet::default()).unwrap();
+            game.explain_automaton()
+        };
+        let loc = Coord { x: 2, y: 2 };
+
+        // A blocker hides the attractor behind it.
+        let why = explain("5x5:2A2/2#2/2@2/5/5");
+        assert_eq!(why.to, loc);
+        assert_eq!(why.axes[0].rays.1.what, Particle::Blocker);
+
+        // A row attractor pulls along its row, but not along its column.
+        let why = explain("5x5:2X2/5/X1@2/5/5");
+        assert_eq!(why.choice, AxisChoice::Urgent(0));
+        assert_eq!(why.to, loc + Delta::XN);
+
+        // A mirror turns the automaton's gaze along the row up the column, toward the attractor.
+        let why = explain("5x5:5/5/2@2/5/2M1A");
+        assert_eq!(why.to, loc + Delta::XP);
+        let ray = &why.axes[0].rays.0;
+        assert_eq!(ray.hit, Some(Coord { x: 4, y: 4 }));
+        assert_eq!((ray.dist, ray.axis, ray.clear), (4, 1, 1));
+        // But the automaton can't step onto the mirror itself.
+        assert_eq!(explain("5x5:5/5/2@2/2M1A/5").to, loc);
+        // A row attractor seen up a column doesn't pull.
+        assert_eq!(explain("5x5:5/5/2@2/5/2M1X").to, loc);
+        // Nor do repulsors either side push it onto a mirror, however far the one seen in the
+        // mirror is.
+        let mut game = Game::new(
+            "5x5:5/2R2/2@2/2M1R/5".parse().unwrap(),
+            2,
+            RuleSet::default(),
+        )
+        .unwrap();
+        let why = game.explain_automaton();
+        assert_eq!(why.axes[0].rays.0.dist, 3);
+        assert_eq!(why.to, loc);
+        game.propose_move(mv(0, c(0, 0), c(0, 1)));
+        game.propose_move(mv(1, c(4, 4), c(4, 3)));
+        game.try_complete_round().unwrap();
+        assert_eq!(game.board.automatons.as_slice(), &[loc]);
+
+        let board: Board = "5x5:#4/M4/2@2/X3Y/x3y".parse().unwrap();
+        assert_eq!(board.to_notation(), "5x5:#4/M4/2@2/X3Y/x3y");
+    }
+
+    #[test]
+    fn terrain() {
+        use smallvec::smallvec;
+
+        // A cross-shaped arena with a hole in one arm, and a wall next to the automaton.
+        let notation = "5x5:_1A1_/5/2@=1/1_3/_3_";
+        let board: Board = notation.parse().unwrap();
+        assert_eq!(board.to_notation(), notation);
+        let mut game = Game::new(board, 2, RuleSet::default()).unwrap();
+
+        // Walls hide what's behind them, just like the edge of the board.
+        let why = game.explain_automaton();
+        assert_eq!(why.axes[1].rays.0.hit, None);
+        assert_eq!(why.axes[1].rays.0.dist, 1);
+        assert_eq!(why.to, c(1, 2));
+
+        // Walls are off-limits, and holes aren't on the board at all.
+        let mut propose = |from, to| {
+            game.propose_move(Move {
+                who: Pid(0),
+                from,
+                to,
+            })
+            .0
+        };
+        assert_eq!(
+            propose(c(2, 3), c(2, 4)),
+            MoveFeedback::SeeCoords(CoordsFeedback {
+                data: smallvec![(c(2, 3), CoordFeedback::Wall), (c(2, 4), CoordFeedback::Ok)],
+            })
+        );
+        assert_eq!(
+            propose(c(3, 2), c(3, 1)),
+            MoveFeedback::SeeCoords(CoordsFeedback {
+                data: smallvec![(c(3, 2), CoordFeedback::Ok), (c(3, 1), CoordFeedback::Oob)],
+            })
+        );
+
+        // Nor can moves pass through either.
+        let mut board = game.board.clone();
+        board.place(c(2, 4), Particle::Attractor);
+        assert_eq!(
+            board.do_move(c(2, 4), c(2, 1), &Rook),
+            MoveResult::OccupiedAt(c(2, 3))
+        );
+        board.place(c(4, 1), Particle::Repulsor);
+        assert_eq!(
+            board.do_move(c(4, 1), c(2, 1), &Rook),
+            MoveResult::OccupiedAt(c(3, 1))
+        );
+    }
+
+    #[test]
+    fn torus() {
+        let explain = |notation: &str| {
+            let board: Board = notation.parse().unwrap();
+            assert_eq!(board.to_notation(), notation);
+            Game::new(board, 2, RuleSet::default())
+                .unwrap()
+                .explain_automaton()
+        };
+
+        // The attractor is nearer the other way round, across the seam.
+        let why = explain("5x5~:5/5/1@2A/5/5");
+        assert_eq!(why.to, c(2, 0));
+        assert_eq!(why.axes[1].rays.1.hit, Some(c(2, 4)));
+        // An empty row leads all the way back round to the automaton itself.
+        let ray = &why.axes[0].rays.0;
+        assert_eq!(
+            (ray.what, ray.hit, ray.dist),
+            (Particle::Automaton, Some(c(2, 1)), 5)
+        );
+        // On a flat board, the attractor is only visible one way.
+        assert_eq!(explain("5x5:5/5/1@2A/5/5").to, c(2, 2));
+        // The automaton can step across the seam too.
+        assert_eq!(explain("5x5~:5/5/@2A1/5/5").to, c(2, 4));
+
+        // Rooks go the short way round.
+        let mut board: Board = "5x5~:A1R2/5/2@2/5/5".parse().unwrap();
+        assert_eq!(board.displacement(c(0, 0), c(0, 4)), Delta::YN);
+        assert_eq!(board.do_move(c(0, 0), c(0, 4), &Rook), MoveResult::Applied);
+        assert_eq!(board.do_move(c(0, 4), c(0, 3), &Rook), MoveResult::Applied);
+        assert_eq!(
+            board.do_move(c(0, 3), c(0, 1), &Rook),
+            MoveResult::OccupiedAt(c(0, 2))
+        );
+
+        let mut board = Board::stock_two_player();
+        board.topology = Topology::Torus;
+        let goals = board.stock_goals(&Roster::new(2)).unwrap();
+        assert_eq!(goals[1], (c(8, 2), Pid(0)));
+        assert_eq!(goals[2], (c(2, 8), Pid(1)));
+    }
+
     #[test]
     fn trapped_all_sides() -> AutMoveTest {
         // TODO
         Ok(())
     }
-}
 
-impl core::fmt::Debug for Board {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        write!(f, "{}x{} board with automaton at {}", self.size.x, self.size.y, self.automaton_location)
+    // The chain tests keep clear of the automaton's row and column (x = 2, y = 2) so that its
+    // step can't disturb the squares under test.
+
+    fn chain_game(players: u8, pieces: &[(Coord, Particle)]) -> Game {
+        let mut game =
+            Game::new(Board::stock_testing_empty(), players, RuleSet::default()).unwrap();
+        for &(at, what) in pieces {
+            game.board.place(at, what);
+        }
+        game
+    }
+
+    /// Submit one move per player, in order, and complete the round.
+    fn play_round(game: &mut Game, moves: &[(Coord, Coord)]) -> Vec<MoveResult> {
+        for (i, &(from, to)) in moves.iter().enumerate() {
+            let who = Pid(i as u8);
+            let (feedback, ready) = game.propose_move(Move { who, from, to });
+            assert_eq!(feedback, MoveFeedback::Committed);
+            assert_eq!(ready, i + 1 == moves.len());
+        }
+        let results = game.try_complete_round().expect("no conflicts");
+        (0..moves.len())
+            .map(|i| {
+                let (_, r) = results.iter().find(|(m, _)| m.who == Pid(i as u8)).unwrap();
+                r.clone()
+            })
+            .collect()
+    }
+
+    fn what(game: &Game, at: Coord) -> Particle {
+        game.board.particles[at.ix()].what()
+    }
+
+    fn assert_unmarked(game: &Game) {
+        assert!(game
+            .board
+            .particles
+            .iter()
+            .all(|c| !c.passable() && !c.conflict()));
+    }
+
+    #[test]
+    fn two_player_chain_vacates_first() {
+        use MoveResult::*;
+        use Particle::*;
+        let mut game = chain_game(2, &[(c(0, 0), Attractor), (c(0, 1), Repulsor)]);
+        let results = play_round(&mut game, &[(c(0, 0), c(0, 1)), (c(0, 1), c(0, 3))]);
+        assert_eq!(results, vec![Applied, Applied]);
+        assert_eq!(what(&game, c(0, 0)), Vacuum);
+        assert_eq!(what(&game, c(0, 1)), Attractor);
+        assert_eq!(what(&game, c(0, 3)), Repulsor);
+        assert_unmarked(&game);
+    }
+
+    #[test]
+    fn two_player_chain_moves_piece_twice() {
+        use MoveResult::*;
+        use Particle::*;
+        let mut game = chain_game(2, &[(c(0, 0), Attractor)]);
+        // Proposed back to front, which shouldn't matter.
+        let results = play_round(&mut game, &[(c(0, 1), c(1, 1)), (c(0, 0), c(0, 1))]);
+        assert_eq!(results, vec![Applied, Applied]);
+        assert_eq!(what(&game, c(0, 0)), Vacuum);
+        assert_eq!(what(&game, c(0, 1)), Vacuum);
+        assert_eq!(what(&game, c(1, 1)), Attractor);
+        assert_unmarked(&game);
+    }
+
+    #[test]
+    fn two_player_cycle_stays_put() {
+        use MoveResult::*;
+        use Particle::*;
+        let mut game = chain_game(2, &[(c(0, 0), Attractor), (c(0, 1), Repulsor)]);
+        let results = play_round(&mut game, &[(c(0, 0), c(0, 1)), (c(0, 1), c(0, 0))]);
+        assert_eq!(results, vec![Cycle, Cycle]);
+        assert_eq!(what(&game, c(0, 0)), Attractor);
+        assert_eq!(what(&game, c(0, 1)), Repulsor);
+        assert_unmarked(&game);
+    }
+
+    #[test]
+    fn three_player_chain() {
+        use MoveResult::*;
+        use Particle::*;
+        let mut game = chain_game(
+            3,
+            &[
+                (c(0, 0), Attractor),
+                (c(0, 1), Repulsor),
+                (c(1, 1), Attractor),
+            ],
+        );
+        let results = play_round(
+            &mut game,
+            &[(c(0, 0), c(0, 1)), (c(0, 1), c(1, 1)), (c(1, 1), c(1, 0))],
+        );
+        assert_eq!(results, vec![Applied, Applied, Applied]);
+        assert_eq!(what(&game, c(0, 0)), Vacuum);
+        assert_eq!(what(&game, c(0, 1)), Attractor);
+        assert_eq!(what(&game, c(1, 1)), Repulsor);
+        assert_eq!(what(&game, c(1, 0)), Attractor);
+        assert_unmarked(&game);
+    }
+
+    #[test]
+    fn three_player_chain_blocked_at_head() {
+        use MoveResult::*;
+        use Particle::*;
+        let mut game = chain_game(
+            3,
+            &[
+                (c(0, 0), Attractor),
+                (c(0, 1), Repulsor),
+                (c(1, 1), Attractor),
+                (c(1, 3), Repulsor),
+            ],
+        );
+        let results = play_round(
+            &mut game,
+            &[(c(0, 0), c(0, 1)), (c(0, 1), c(1, 1)), (c(1, 1), c(1, 4))],
+        );
+        assert_eq!(
+            results,
+            vec![
+                OccupiedAt(c(0, 1)),
+                OccupiedAt(c(1, 1)),
+                OccupiedAt(c(1, 3))
+            ]
+        );
+        assert_eq!(what(&game, c(0, 0)), Attractor);
+        assert_eq!(what(&game, c(0, 1)), Repulsor);
+        assert_eq!(what(&game, c(1, 1)), Attractor);
+        assert_eq!(what(&game, c(1, 4)), Vacuum);
+        assert_unmarked(&game);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn four_player_cycle_stays_put() {
+        use MoveResult::*;
+        use Particle::*;
+        let pieces = [
+            (c(0, 0), Attractor),
+            (c(0, 1), Repulsor),
+            (c(1, 1), Attractor),
+            (c(1, 0), Repulsor),
+        ];
+        let mut game = chain_game(4, &pieces);
+        let results = play_round(
+            &mut game,
+            &[
+                (c(0, 0), c(0, 1)),
+                (c(0, 1), c(1, 1)),
+                (c(1, 1), c(1, 0)),
+                (c(1, 0), c(0, 0)),
+            ],
+        );
+        assert_eq!(results, vec![Cycle, Cycle, Cycle, Cycle]);
+        for &(at, w) in &pieces {
+            assert_eq!(what(&game, at), w);
+        }
+        assert_unmarked(&game);
+    }
+
+    #[test]
+    fn four_player_chain_with_relay() {
+        use MoveResult::*;
+        use Particle::*;
+        let mut game = chain_game(4, &[(c(0, 0), Attractor), (c(1, 3), Repulsor)]);
+        // The attractor relays through two empty sources, stopping where the repulsor left.
+        let results = play_round(
+            &mut game,
+            &[
+                (c(0, 0), c(0, 1)),
+                (c(0, 1), c(1, 1)),
+                (c(1, 1), c(1, 3)),
+                (c(1, 3), c(3, 3)),
+            ],
+        );
+        assert_eq!(results, vec![Applied, Applied, Applied, Applied]);
+        assert_eq!(what(&game, c(0, 0)), Vacuum);
+        assert_eq!(what(&game, c(0, 1)), Vacuum);
+        assert_eq!(what(&game, c(1, 1)), Vacuum);
+        assert_eq!(what(&game, c(1, 3)), Attractor);
+        assert_eq!(what(&game, c(3, 3)), Repulsor);
+        assert_unmarked(&game);
+    }
+
+    #[test]
+    fn chain_without_a_piece() {
+        use MoveResult::*;
+        let mut game = chain_game(2, &[]);
+        let results = play_round(&mut game, &[(c(0, 0), c(0, 1)), (c(0, 1), c(1, 1))]);
+        assert_eq!(results, vec![NoSource, NoSource]);
+    }
+
+    #[test]
+    fn conflicts_lock_the_other_players() {
+        let mut game = chain_game(
+            3,
+            &[
+                (c(0, 0), Particle::Attractor),
+                (c(0, 2), Particle::Repulsor),
+                (c(4, 4), Particle::Attractor),
+            ],
+        );
+        game.propose_move(mv(0, c(0, 0), c(0, 1)));
+        game.propose_move(mv(1, c(0, 2), c(0, 1)));
+        game.propose_move(mv(2, c(4, 4), c(4, 3)));
+        assert!(game.try_complete_round().is_err());
+
+        // The conflicted players choose again; the other's move stands, and they must wait.
+        assert_eq!(game.locked_players.as_slice(), &[Pid(2)]);
+        assert_eq!(game.pending_moves.as_slice(), &[mv(2, c(4, 4), c(4, 3))]);
+        let (feedback, _) = game.propose_move(mv(2, c(4, 4), c(3, 4)));
+        assert_eq!(feedback, MoveFeedback::WaitYourTurn);
+        let (feedback, _) = game.propose_move(mv(0, c(0, 0), c(1, 0)));
+        assert_eq!(feedback, MoveFeedback::Committed);
+        let (feedback, ready) = game.propose_move(mv(1, c(0, 2), c(1, 2)));
+        assert_eq!(feedback, MoveFeedback::Committed);
+        assert!(ready);
+
+        assert!(game.try_complete_round().is_ok());
+        assert!(game.locked_players.is_empty());
+        assert!(!game.board.is_vacuum(c(4, 3)));
+    }
+
+    #[test]
+    fn revising_replaces_pending_move() {
+        let mut game = chain_game(2, &[(c(0, 0), Particle::Attractor)]);
+        let first = mv(0, c(0, 0), c(0, 1));
+        let second = Move {
+            to: c(1, 0),
+            ..first
+        };
+        assert_eq!(game.propose_move(first), (MoveFeedback::Committed, false));
+        assert_eq!(game.round, RoundState::PartiallySubmitted);
+        assert_eq!(game.propose_move(second), (MoveFeedback::Replaced, false));
+        assert_eq!(game.pending_moves.as_slice(), &[second]);
+
+        let other = mv(1, c(4, 4), c(4, 3));
+        assert_eq!(game.propose_move(other), (MoveFeedback::Committed, true));
+        assert_eq!(game.pending_moves.len(), 2);
+    }
+
+    #[test]
+    fn withdrawing_pending_move() {
+        let mut game = chain_game(2, &[]);
+        let m = mv(0, c(0, 0), c(0, 1));
+        game.propose_move(m);
+        assert_eq!(game.withdraw_move(Pid(1)), None);
+        assert_eq!(game.withdraw_move(Pid(0)), Some(m));
+        assert_eq!(game.withdraw_move(Pid(0)), None);
+        assert!(game.pending_moves.is_empty());
+        assert_eq!(game.round, RoundState::Fresh);
+    }
+
+    #[test]
+    fn locked_move_cannot_be_revised() {
+        let mut game = chain_game(3, &[]);
+        game.propose_move(mv(0, c(0, 0), c(0, 1)));
+        game.propose_move(mv(1, c(1, 0), c(0, 0)));
+        game.propose_move(mv(2, c(4, 4), c(4, 3)));
+        game.propose_move(mv(1, c(1, 1), c(0, 1)));
+        assert!(game.try_complete_round().is_err());
+        assert_eq!(game.round, RoundState::ResolvingConflict);
+        assert_eq!(game.locked_players.as_slice(), &[Pid(2)]);
+
+        assert_eq!(game.withdraw_move(Pid(2)), None);
+        let (feedback, _) = game.propose_move(mv(2, c(4, 4), c(3, 4)));
+        assert_eq!(feedback, MoveFeedback::WaitYourTurn);
+
+        let (feedback, _) = game.propose_move(mv(0, c(0, 0), c(0, 3)));
+        assert_eq!(feedback, MoveFeedback::Committed);
+        let (feedback, ready) = game.propose_move(mv(1, c(1, 0), c(1, 1)));
+        assert_eq!(feedback, MoveFeedback::Committed);
+        assert!(ready);
+    }
+
+    #[test]
+    fn events_are_opt_in() {
+        let mut game = chain_game(2, &[]);
+        play_round(&mut game, &[(c(0, 0), c(0, 1)), (c(4, 4), c(4, 3))]);
+        assert!(game.events.is_empty());
+
+        game.emit_events = true;
+        let mut quiet = game.clone();
+        quiet.emit_events = false;
+        play_round(&mut game, &[(c(0, 1), c(0, 2)), (c(4, 3), c(4, 2))]);
+        play_round(&mut quiet, &[(c(0, 1), c(0, 2)), (c(4, 3), c(4, 2))]);
+        assert!(quiet.events.is_empty());
+        assert!(game.drain_events().next().is_some());
+    }
+
+    #[test]
+    fn events_for_a_conflicted_round() {
+        use GameEvent::*;
+        let mut game = chain_game(2, &[(c(0, 0), Particle::Attractor)]);
+        game.emit_events = true;
+        // Pull the automaton into player 1's goal.
+        game.board.place(c(2, 0), Particle::Attractor);
+        game.goals.push((c(2, 1), Pid(1)));
+        let ack = |m| MoveAck { m, replaced: false };
+
+        game.propose_move(mv(0, c(0, 0), c(0, 1)));
+        game.propose_move(mv(1, c(1, 1), c(0, 1)));
+        assert!(game.try_complete_round().is_err());
+        let events: Vec<_> = game.drain_events().collect();
+        assert_eq!(
+            events,
+            vec![
+                ack(mv(0, c(0, 0), c(0, 1))),
+                ack(mv(1, c(1, 1), c(0, 1))),
+                Conflict {
+                    at: c(0, 1),
+                    players: SmallVec::from_slice(&[Pid(0), Pid(1)]),
+                },
+            ]
+        );
+
+        game.propose_move(mv(0, c(0, 0), c(0, 1)));
+        game.propose_move(mv(0, c(0, 0), c(0, 3)));
+        game.propose_move(mv(1, c(4, 4), c(4, 3)));
+        assert!(game.try_complete_round().is_ok());
+        let events: Vec<_> = game.drain_events().collect();
+        assert_eq!(
+            events,
+            vec![
+                MoveInvalid {
+                    who: Pid(0),
+                    feedback: MoveFeedback::SeeCoords(CoordsFeedback {
+                        data: SmallVec::from_vec(vec![
+                            (c(0, 0), CoordFeedback::Ok),
+                            (c(0, 1), CoordFeedback::Conflict),
+                        ]),
+                    }),
+                },
+                ack(mv(0, c(0, 0), c(0, 3))),
+                ack(mv(1, c(4, 4), c(4, 3))),
+                DoMove {
+                    m: mv(0, c(0, 0), c(0, 3)),
+                    result: MoveResult::Applied,
+                },
+                DoMove {
+                    m: mv(1, c(4, 4), c(4, 3)),
+                    result: MoveResult::NoSource,
+                },
+                AutomatonMove {
+                    from: c(2, 2),
+                    to: c(2, 1),
+                },
+                TurnOver {
+                    automatons_from: SmallVec::from_buf([c(2, 2)]),
+                    automatons_to: SmallVec::from_buf([c(2, 1)]),
+                    winner: Some(Pid(1)),
+                },
+            ]
+        );
+        assert!(game.drain_events().next().is_none());
+    }
+
+    #[test]
+    fn undo_and_redo_rounds() {
+        use Particle::*;
+        let mut game = chain_game(2, &[(c(0, 0), Attractor)]);
+        let start = game.board.clone();
+        assert!(!game.undo_round());
+        play_round(&mut game, &[(c(0, 0), c(0, 1)), (c(4, 4), c(4, 3))]);
+        let after = game.board.clone();
+        assert_eq!(game.history().len(), 1);
+        assert_eq!(game.history()[0].before, start);
+
+        game.propose_move(mv(0, c(0, 1), c(0, 3)));
+        assert!(game.undo_round());
+        assert_eq!(game.board, start);
+        assert!(game.history().is_empty());
+        assert!(game.pending_moves.is_empty());
+        assert_eq!(game.round, RoundState::Fresh);
+
+        assert!(game.redo_round());
+        assert_eq!(game.board, after);
+        assert_eq!(game.history().len(), 1);
+        assert!(!game.redo_round());
+
+        // Playing on forgets what could have been redone.
+        assert!(game.undo_round());
+        play_round(&mut game, &[(c(0, 0), c(0, 3)), (c(4, 4), c(4, 3))]);
+        assert!(!game.redo_round());
+        assert_eq!(what(&game, c(0, 3)), Attractor);
+    }
+
+    #[test]
+    fn undo_a_winning_round() {
+        let mut game = chain_game(2, &[(c(2, 0), Particle::Attractor)]);
+        game.emit_events = true;
+        game.goals.push((c(2, 1), Pid(1)));
+        play_round(&mut game, &[(c(0, 0), c(0, 1)), (c(4, 4), c(4, 3))]);
+        assert_eq!(game.winner, Some(Pid(1)));
+
+        assert!(game.undo_round());
+        assert_eq!(game.winner, None);
+        assert_eq!(game.round, RoundState::Fresh);
+        assert!(game.redo_round());
+        assert_eq!(game.winner, Some(Pid(1)));
+        assert_eq!(game.round, RoundState::GameOver);
+        let events: Vec<_> = game.drain_events().collect();
+        assert_eq!(
+            events[events.len() - 2..],
+            [GameEvent::RoundUndone, GameEvent::RoundRedone]
+        );
+    }
+
+    #[test]
+    fn automatons_step_in_order() {
+        use GameEvent::AutomatonMove;
+        // Both automatons want (1, 2); the one at (1, 1) steps first and gets it.
+        let board: Board = "5x5:5/1@1A1/2@2/2R2/5".parse().unwrap();
+        let mut game = Game::new(board, 2, RuleSet::default()).unwrap();
+        game.emit_events = true;
+        game.goals.push((c(2, 2), Pid(1)));
+        game.goals.push((c(1, 2), Pid(0)));
+
+        let why = game.explain_automatons();
+        assert_eq!(why.len(), 2);
+        assert_eq!((why[0].from, why[0].to), (c(1, 1), c(1, 2)));
+        assert_eq!((why[1].from, why[1].to), (c(2, 2), c(2, 2)));
+        assert_eq!(why[1].axes[0].rays.1.what, Particle::Automaton);
+        // On its own, the second would have moved too.
+        assert!(matches!(
+            game.explain_step(&game.board, c(2, 2)).axes[0].decision,
+            AutomatonDecision::FromRepulsor { pos: false, .. }
+        ));
+
+        play_round(&mut game, &[(c(0, 0), c(0, 1)), (c(4, 4), c(4, 3))]);
+        assert_eq!(game.board.automatons.as_slice(), &[c(1, 2), c(2, 2)]);
+        let steps: Vec<_> = game
+            .drain_events()
+            .filter(|e| matches!(e, AutomatonMove { .. }))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                AutomatonMove {
+                    from: c(1, 1),
+                    to: c(1, 2),
+                },
+                AutomatonMove {
+                    from: c(2, 2),
+                    to: c(2, 2),
+                },
+            ]
+        );
+        // Both automatons are on goals, and the first decides.
+        assert_eq!(game.winner, Some(Pid(0)));
+    }
+
+    #[test]
+    fn packed_cells() {
+        use Particle::*;
+        assert_eq!(std::mem::size_of::<Cell>(), 1);
+        for (i, &what) in Particle::ALL.iter().enumerate() {
+            assert_eq!(what as usize, i);
+            for &terrain in &[Terrain::Floor, Terrain::Wall, Terrain::Hole] {
+                for &(conflict, passable) in
+                    &[(false, false), (true, false), (false, true), (true, true)]
+                {
+                    let mut cell = Cell::new(Vacuum).with_terrain(terrain);
+                    cell.set_what(what);
+                    cell.set_conflict(conflict);
+                    cell.set_passable(passable);
+                    assert_eq!(
+                        (
+                            cell.what(),
+                            cell.conflict(),
+                            cell.passable(),
+                            cell.terrain()
+                        ),
+                        (what, conflict, passable, terrain)
+                    );
+                    cell.set_conflict(false);
+                    cell.set_what(Vacuum);
+                    assert_eq!(cell.terrain(), terrain);
+                    assert_eq!(cell.passable(), passable);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn placing_automatons() {
+        let mut board = Board::stock_testing_empty();
+        board.place(c(4, 0), Particle::Automaton);
+        board.place(c(0, 4), Particle::Automaton);
+        assert_eq!(board.automatons.as_slice(), &[c(0, 4), c(2, 2), c(4, 0)]);
+        board.place(c(2, 2), Particle::Attractor);
+        assert_eq!(board.automatons.as_slice(), &[c(0, 4), c(4, 0)]);
+        board.force_move(c(0, 4), c(4, 4));
+        assert_eq!(board.automatons.as_slice(), &[c(4, 0), c(4, 4)]);
+        assert_eq!(
+            format!("{:?}", board),
+            "5x5 board with automatons at (4, 0), (4, 4)"
+        );
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn coord_arithmetic(x: u16, y: u16, dx in -70000i32..70000, dy in -70000i32..70000) {
+            let (from, d) = (c(x, y), Delta::new(dx, dy));
+            let fits = |at: u16, by: i32| (0..=u16::MAX as i64).contains(&(at as i64 + by as i64));
+            match from.checked_add(d) {
+                Some(to) => {
+                    assert!(fits(x, dx) && fits(y, dy));
+                    assert_eq!(to - from, d);
+                }
+                None => assert!(!fits(x, dx) || !fits(y, dy)),
+            }
+        }
+
+        #[test]
+        fn offsets_at_the_edges(
+            w in 1u16.., h in 1u16.., fx: u16, fy: u16, tx: u16, ty: u16, torus: bool
+        ) {
+            // Offsets don't look at the cells, so an empty grid will do.
+            let mut board = Board::stock_testing_empty();
+            board.size = c(w, h);
+            if torus {
+                board.topology = Topology::Torus;
+            }
+            let (from, to) = (c(fx % w, fy % h), c(tx % w, ty % h));
+            let d = board.displacement(from, to);
+            assert_eq!(board.offset(from, d), Some(to));
+            assert_eq!(board.offset(from, Delta::new(w as i32 - from.x as i32, 0)), if torus {
+                Some(c(0, from.y))
+            } else {
+                None
+            });
+            assert_eq!(board.offset(from, Delta::new(0, -(from.y as i32) - 1)), if torus {
+                Some(c(from.x, h - 1))
+            } else {
+                None
+            });
+        }
+
+        #[test]
+        fn long_rows(w in 200u16.., a: u16, b: u16) {
+            let (a, b) = (a % w, b % w);
+            proptest::prop_assume!(a != b && a != w / 2 && b != w / 2);
+            let mut board = Board::new(w, 1);
+            board.place(c(a, 0), Particle::Attractor);
+
+            let (dir, edge) = if a > w / 2 { (Delta::XP, w - 1 - a) } else { (Delta::XN, a) };
+            let ray = board.raycast(c(w / 2, 0), dir);
+            assert_eq!(ray.hit, Some(c(a, 0)));
+            assert_eq!(ray.dist, (a as i32 - (w / 2) as i32).unsigned_abs() as usize);
+            let ray = board.raycast(c(a, 0), dir);
+            assert_eq!((ray.hit, ray.dist), (None, edge as usize + 1));
+
+            // The attractor can slide anywhere up to the automaton, but not past it.
+            let blocked = (a < w / 2) != (b < w / 2);
+            let result = board.do_move(c(a, 0), c(b, 0), &Rook);
+            if blocked {
+                assert_eq!(result, MoveResult::OccupiedAt(c(w / 2, 0)));
+            } else {
+                assert_eq!(result, MoveResult::Applied);
+                assert_eq!(board.particles[c(b, 0).ix()].what(), Particle::Attractor);
+            }
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! Shorthands shared by the tests.
+
+use crate::*;
+
+pub(crate) fn c(x: u16, y: u16) -> Coord {
+    Coord { x, y }
+}
+
+pub(crate) fn mv(who: u8, from: Coord, to: Coord) -> Move {
+    Move {
+        who: Pid(who),
+        from,
+        to,
+    }
+}
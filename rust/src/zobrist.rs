@@ -0,0 +1,151 @@
+//! Zobrist hashing of positions, for transposition tables and spotting repeated positions.
+//!
+//! Everything a position is made of, like a particle on a cell or a player's goal, has its own
+//! pseudo-random key, and a position's hash is the XOR of the keys of everything in it. So moving a
+//! particle changes the hash by a couple of XORs, and the board keeps its hash up to date as it
+//! changes instead of going over the whole grid.
+//!
+//! The keys are worked out from what they stand for rather than looked up in a table, so boards of
+//! any size can be hashed, and a position hashes the same from one run of the program to the next.
+
+use crate::*;
+use ndarray::Array2 as Grid;
+
+/// The different kinds of thing that have keys, so they don't share any.
+#[derive(Clone, Copy)]
+enum Kind {
+    Particle,
+    Terrain,
+    Round,
+    Goal,
+}
+
+/// The key for a thing of some kind, with a value, at a coordinate.
+///
+/// This is the finalizer of SplitMix64, which spreads every bit of its input over the output.
+fn key(kind: Kind, value: u8, at: Coord) -> u64 {
+    let mut z = (kind as u64) << 40 | (value as u64) << 32 | (at.x as u64) << 16 | at.y as u64;
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// The part of a board's hash for one cell: its particle and its terrain. Empty floor is zero.
+pub(crate) fn cell_key(c: Coord, cell: Cell) -> u64 {
+    let mut hash = 0;
+    if !cell.what().is_vacuum() {
+        hash ^= key(Kind::Particle, cell.what() as u8, c);
+    }
+    if cell.terrain() != Terrain::Floor {
+        hash ^= key(Kind::Terrain, cell.terrain() as u8, c);
+    }
+    hash
+}
+
+/// The hash of a whole grid of cells, from scratch.
+pub(crate) fn hash_cells(particles: &Grid<Cell>) -> u64 {
+    particles
+        .indexed_iter()
+        .map(|((x, y), &cell)| {
+            let c = Coord {
+                x: x as u16,
+                y: y as u16,
+            };
+            cell_key(c, cell)
+        })
+        .fold(0, |hash, key| hash ^ key)
+}
+
+impl Board {
+    /// A hash of the position: which particle is on each cell, the automatons included, and the
+    /// terrain. Equal positions on boards of the same shape hash the same, however they came about.
+    ///
+    /// This is kept up to date as the board changes, so it costs nothing to ask for.
+    pub fn zobrist(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl Game {
+    /// A hash of the game's state: the board's position (see `Board::zobrist`), the round state
+    /// and the goals. The pending moves, the roster and the history aren't included.
+    pub fn zobrist(&self) -> u64 {
+        let round = key(Kind::Round, self.round as u8, Coord { x: 0, y: 0 });
+        self.goals
+            .iter()
+            .map(|&(c, pid)| key(Kind::Goal, pid.0, c))
+            .fold(self.board.zobrist() ^ round, |hash, key| hash ^ key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitboard::tests::{apply_edits, boards, edits};
+    use crate::testutil::{c, mv};
+    use crate::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn transpositions() {
+        let stock = Board::stock_two_player();
+        assert_eq!(stock.zobrist(), Board::stock_two_player().zobrist());
+        assert_ne!(stock.zobrist(), Board::stock_testing().zobrist());
+        assert_ne!(stock.zobrist(), 0);
+
+        // The same two moves in either order make the same position.
+        let (mut one, mut other) = (stock.clone(), stock.clone());
+        one.force_move(c(0, 1), c(0, 2));
+        one.force_move(c(10, 1), c(10, 2));
+        other.force_move(c(10, 1), c(10, 2));
+        assert_ne!(other.zobrist(), one.zobrist());
+        other.force_move(c(0, 1), c(0, 2));
+        assert_eq!(other.zobrist(), one.zobrist());
+
+        // Back and forth again is a repetition.
+        one.force_move(c(0, 2), c(0, 1));
+        one.force_move(c(0, 1), c(0, 2));
+        assert_eq!(other.zobrist(), one.zobrist());
+        let notation = one.to_notation();
+        assert_eq!(
+            Board::from_notation(&notation).unwrap().zobrist(),
+            one.zobrist()
+        );
+
+        // Swapping a repulsor for an attractor changes it.
+        one.place(c(0, 2), Particle::Attractor);
+        assert_ne!(other.zobrist(), one.zobrist());
+    }
+
+    #[test]
+    fn game_state() {
+        let mut game =
+            Game::with_stock_goals(Board::stock_two_player(), 2, RuleSet::default()).unwrap();
+        let fresh = game.zobrist();
+        assert_ne!(fresh, game.board.zobrist());
+
+        game.propose_move(mv(0, c(0, 1), c(0, 2)));
+        let submitted = game.zobrist();
+        assert_ne!(submitted, fresh);
+        assert_eq!(game.board.zobrist(), Board::stock_two_player().zobrist());
+
+        // The same board with the goals handed out the other way round is a different game.
+        let mut swapped = game.clone();
+        for (_, pid) in &mut swapped.goals {
+            pid.0 = 1 - pid.0;
+        }
+        assert_ne!(swapped.zobrist(), submitted);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn kept_up_to_date(board in boards(), edits in edits()) {
+            apply_edits(board, edits, |board, _| {
+                prop_assert_eq!(board.zobrist(), super::hash_cells(&board.particles));
+                Ok(())
+            })?;
+        }
+    }
+}
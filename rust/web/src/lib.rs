@@ -19,18 +19,28 @@ fn cell(c: Coord) {
         match p {
             Repulsor => Attractor,
             Attractor => Automaton,
-            Automaton => Vacuum,
+            Automaton => Blocker,
+            Blocker => Mirror,
+            Mirror => RowAttractor,
+            RowAttractor => ColumnAttractor,
+            ColumnAttractor => RowRepulsor,
+            RowRepulsor => ColumnRepulsor,
+            ColumnRepulsor => Vacuum,
             Vacuum => Repulsor,
         }
     }
     let cell: Cell = game.board.particles[c.ix()];
     let on_click = move |_: event::Click| {
+        if cell.terrain() != Terrain::Floor {
+            return;
+        }
         let mut g = Game::clone(&*game); // holy shit
-        g.board.particles[c.ix()].what = next_particle(cell.what);
+        // Through place, so that the board keeps track of its automatons.
+        g.board.place(c, next_particle(cell.what()));
         game.set(g); // how can we avoid this
     };
     mox! {<div on={on_click }
-    class={format!("cell passable-{} conflict-{}", cell.passable, cell.conflict)}><div style="display:inline-block"><img src={format!("img/{:?}.png", cell.what)}/></div></div>}
+    class={format!("cell passable-{} conflict-{} terrain-{:?}", cell.passable(), cell.conflict(), cell.terrain())}><div style="display:inline-block"><img src={format!("img/{:?}.png", cell.what())}/></div></div>}
 }
 
 #[topo::nested]
@@ -43,7 +53,7 @@ fn game_board() {
 
         <button on={move |_: event::Click| {
             let mut g = Game::clone(&button_game);
-            g.update_automaton();
+            g.update_automaton(); // steps every automaton on the board
             button_game.set(g);
         }}>"button time!"</button>
 
@@ -68,7 +78,9 @@ fn game_board() {
 
 #[topo::nested]
 fn automatafl_game() {
-    let game = state(|| Game::new(Board::stock_two_player(), 2, true));
+    let game = state(|| {
+        Game::with_stock_goals(Board::stock_two_player(), 2, RuleSet::default()).unwrap()
+    });
 
     illicit::child_env![
         Key<Game> => game